@@ -0,0 +1,333 @@
+use std::fmt;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+
+use crate::king_safety::{self, KingSafetyWeights};
+use crate::mobility::{self, MobilityWeights};
+use crate::pawns::{self, PawnWeights};
+use crate::pst::{self, PieceValues, Tables};
+use crate::schach::{piece_value, Color, Piece, Schach, PIECE_VALUES};
+use crate::score::Score;
+
+// Statische Bewertung einer Stellung aus Sicht von Weiß. Matt und Remis erkennen die Suche bzw.
+// eval_position_with vorher, die Bewertung sieht nur die Figuren auf dem Brett
+pub trait Evaluator: Sync {
+    fn evaluate(&self, brett: &Schach) -> Score;
+
+    // Wird vor der Suche mit dem Brett jedes Suchthreads aufgerufen, z.B. damit es seine Summen mit
+    // den eigenen Material- und Feldwerten mitführt
+    fn prepare(&self, _brett: &mut Schach) {}
+}
+
+lazy_static! {
+    pub static ref HAND_CRAFTED: HandCrafted = HandCrafted::default();
+}
+
+// Reihenfolge wie in Piece
+const PIECE_NAMES: [&str; 6] = ["king", "queen", "rook", "bishop", "knight", "pawn"];
+const SECTIONS: [&str; 5] = ["material", "pst", "pawns", "king_safety", "mobility"];
+
+// Alle Gewichte der Bewertung. In einer Datei müssen nur die geänderten Werte stehen, z.B.
+// { "material": { "knight": 320 }, "pawns": { "doubled": [-20, -30] } }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Weights {
+    pub material: [i32; 6],
+    pub pst: Tables,
+    pub pawns: PawnWeights,
+    pub king_safety: KingSafetyWeights,
+    pub mobility: MobilityWeights,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        let pieces = [Piece::King, Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn];
+        Weights {
+            material: pieces.map(|p| piece_value(&p)),
+            pst: Tables::default(),
+            pawns: PawnWeights::default(),
+            king_safety: KingSafetyWeights::default(),
+            mobility: MobilityWeights::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WeightsError {
+    Io(String),
+    Syntax(String),
+    UnknownKey(String),
+    InvalidValue { key: String, expected: String },
+}
+
+impl fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WeightsError::Io(message) => write!(f, "could not read weights: {}", message),
+            WeightsError::Syntax(error) => write!(f, "invalid weights file: {}", error),
+            WeightsError::UnknownKey(key) => write!(f, "unknown weight '{}'", key),
+            WeightsError::InvalidValue { key, expected } => write!(f, "invalid value for '{}': expected {}", key, expected),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+impl Weights {
+    pub fn load(path: &str) -> Result<Weights, WeightsError> {
+        let text = std::fs::read_to_string(path).map_err(|e| WeightsError::Io(format!("{}: {}", path, e)))?;
+        Weights::from_json(&text)
+    }
+
+    // Nicht angegebene Werte bleiben bei ihrer Voreinstellung
+    pub fn from_json(text: &str) -> Result<Weights, WeightsError> {
+        let json: Value = serde_json::from_str(text).map_err(|e| WeightsError::Syntax(e.to_string()))?;
+        let mut weights = Weights::default();
+        let mut fields = fields(&mut weights);
+        for (section, entries) in object(&json, "")? {
+            for (key, value) in object(entries, section)? {
+                let name = format!("{}.{}", section, key);
+                let Some((_, _, targets)) = fields.iter_mut().find(|(s, k, _)| s == section && k == key) else {
+                    return Err(WeightsError::UnknownKey(name));
+                };
+                let values = match value {
+                    Value::Array(values) if targets.len() > 1 => values.iter().collect(),
+                    Value::Number(_) if targets.len() == 1 => vec![value],
+                    _ => vec![],
+                };
+                let len = targets.len();
+                if values.len() != len {
+                    return Err(invalid(&name, len));
+                }
+                for (target, value) in targets.iter_mut().zip(values) {
+                    **target = integer(value).ok_or_else(|| invalid(&name, len))?;
+                }
+            }
+        }
+        Ok(weights)
+    }
+
+    // Vorlage für eine eigene Datei mit allen Werten
+    pub fn to_json(&self) -> String {
+        let mut weights = self.clone();
+        let fields = fields(&mut weights);
+        let sections: Vec<String> = SECTIONS.iter().map(|section| {
+            let entries: Vec<String> = fields.iter()
+                .filter(|(s, _, _)| s == section)
+                .map(|(_, key, values)| format!("    \"{}\": {}", key, format_values(values)))
+                .collect();
+            format!("  \"{}\": {{\n{}\n  }}", section, entries.join(",\n"))
+        }).collect();
+        format!("{{\n{}\n}}\n", sections.join(",\n"))
+    }
+}
+
+// Jeder einstellbare Wert als (Abschnitt, Name, Zahlen). Einzelwerte stehen in der Datei als Zahl,
+// alles andere als Liste, Paare als [Mittelspiel, Endspiel]
+fn fields(weights: &mut Weights) -> Vec<(&'static str, String, Vec<&mut i32>)> {
+    let Weights { material, pst, pawns, king_safety, mobility } = weights;
+    let mut fields = Vec::new();
+    for (name, value) in PIECE_NAMES.iter().zip(material.iter_mut()) {
+        fields.push(("material", name.to_string(), vec![value]));
+    }
+    for ((name, mg), eg) in PIECE_NAMES.iter().zip(pst.mg.iter_mut()).zip(pst.eg.iter_mut()) {
+        fields.push(("pst", format!("{}_mg", name), mg.iter_mut().collect()));
+        fields.push(("pst", format!("{}_eg", name), eg.iter_mut().collect()));
+    }
+
+    let PawnWeights { doubled, isolated, backward, connected, passed_mg, passed_eg } = pawns;
+    fields.push(("pawns", "doubled".to_string(), pair(doubled)));
+    fields.push(("pawns", "isolated".to_string(), pair(isolated)));
+    fields.push(("pawns", "backward".to_string(), pair(backward)));
+    fields.push(("pawns", "connected".to_string(), connected.iter_mut().collect()));
+    fields.push(("pawns", "passed_mg".to_string(), passed_mg.iter_mut().collect()));
+    fields.push(("pawns", "passed_eg".to_string(), passed_eg.iter_mut().collect()));
+
+    let KingSafetyWeights { shield, open_file, storm, knight_attack, bishop_attack, rook_attack, queen_attack, attack_weight } = king_safety;
+    fields.push(("king_safety", "shield".to_string(), shield.iter_mut().collect()));
+    fields.push(("king_safety", "open_file".to_string(), vec![open_file]));
+    fields.push(("king_safety", "storm".to_string(), storm.iter_mut().collect()));
+    fields.push(("king_safety", "knight_attack".to_string(), vec![knight_attack]));
+    fields.push(("king_safety", "bishop_attack".to_string(), vec![bishop_attack]));
+    fields.push(("king_safety", "rook_attack".to_string(), vec![rook_attack]));
+    fields.push(("king_safety", "queen_attack".to_string(), vec![queen_attack]));
+    fields.push(("king_safety", "attack_weight".to_string(), attack_weight.iter_mut().collect()));
+
+    let m = mobility;
+    for (name, (base, mg, eg)) in [("knight", &mut m.knight), ("bishop", &mut m.bishop), ("rook", &mut m.rook), ("queen", &mut m.queen)] {
+        fields.push(("mobility", name.to_string(), vec![base, mg, eg]));
+    }
+    for (name, value) in [
+        ("rook_open_file", &mut m.rook_open_file),
+        ("rook_semi_open_file", &mut m.rook_semi_open_file),
+        ("rook_on_seventh", &mut m.rook_on_seventh),
+        ("bishop_pair", &mut m.bishop_pair),
+        ("knight_outpost", &mut m.knight_outpost),
+        ("trapped_minor", &mut m.trapped_minor),
+        ("trapped_rook", &mut m.trapped_rook),
+    ] {
+        fields.push(("mobility", name.to_string(), pair(value)));
+    }
+    fields
+}
+
+fn pair((mg, eg): &mut (i32, i32)) -> Vec<&mut i32> {
+    vec![mg, eg]
+}
+
+fn object<'a>(json: &'a Value, key: &str) -> Result<&'a Map<String, Value>, WeightsError> {
+    match json {
+        Value::Object(entries) => Ok(entries),
+        _ => Err(WeightsError::InvalidValue { key: key.to_string(), expected: "an object".to_string() }),
+    }
+}
+
+fn integer(json: &Value) -> Option<i32> {
+    json.as_i64().and_then(|n| i32::try_from(n).ok())
+}
+
+fn invalid(key: &str, len: usize) -> WeightsError {
+    let expected = match len {
+        1 => "an integer".to_string(),
+        _ => format!("a list of {} integers", len),
+    };
+    WeightsError::InvalidValue { key: key.to_string(), expected }
+}
+
+// Feldtabellen mit acht Werten pro Zeile, wie im Quelltext
+fn format_values(values: &[&mut i32]) -> String {
+    let numbers: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    match numbers.len() {
+        1 => numbers[0].clone(),
+        64 => {
+            let rows: Vec<String> = numbers.chunks(8).map(|row| format!("      {}", row.join(", "))).collect();
+            format!("[\n{}\n    ]", rows.join(",\n"))
+        },
+        _ => format!("[{}]", numbers.join(", ")),
+    }
+}
+
+// Die handgeschriebene Bewertung: Material, Feldtabellen, Bauernstruktur, Königssicherheit und Beweglichkeit
+pub struct HandCrafted {
+    weights: Weights,
+    // Material und Feldtabellen aus weights für die Summen, die das Brett mitführt
+    piece_values: Arc<PieceValues>,
+}
+
+impl HandCrafted {
+    pub fn new(weights: Weights) -> Self {
+        let piece_values = PieceValues::new(&weights.material, &weights.pst);
+        // Mit den eingebauten Werten kann jedes Brett seine Summen behalten
+        let piece_values = match piece_values == **PIECE_VALUES {
+            true => Arc::clone(&PIECE_VALUES),
+            false => Arc::new(piece_values),
+        };
+        HandCrafted { weights, piece_values }
+    }
+
+    pub fn weights(&self) -> &Weights {
+        &self.weights
+    }
+}
+
+impl Default for HandCrafted {
+    fn default() -> Self {
+        HandCrafted::new(Weights::default())
+    }
+}
+
+impl Evaluator for HandCrafted {
+    fn evaluate(&self, brett: &Schach) -> Score {
+        let weights = &self.weights;
+        // Ein Brett ohne prepare, z.B. außerhalb der Suche, wird einmal mit den eigenen Werten umgerechnet
+        let (material_mg, material_eg) = match Arc::ptr_eq(brett.piece_values(), &self.piece_values) {
+            true => brett.material_and_pst(),
+            false => {
+                let mut brett = brett.clone();
+                self.prepare(&mut brett);
+                brett.material_and_pst()
+            },
+        };
+        let pawns = pawns::probe(brett.bitboards(&Color::White).5, brett.bitboards(&Color::Black).5, &weights.pawns);
+        let (passed_mg, passed_eg) = pawns::passed_bonus(&pawns, brett.occupied(), &weights.pawns);
+        let (king_mg, king_eg) = king_safety::evaluate(brett, &weights.king_safety);
+        let (mobility_mg, mobility_eg) = mobility::evaluate(brett, &weights.mobility);
+        let mg = material_mg + pawns.mg + passed_mg + king_mg + mobility_mg;
+        let eg = material_eg + pawns.eg + passed_eg + king_eg + mobility_eg;
+        Score(pst::taper(mg, eg, brett.phase()))
+    }
+
+    fn prepare(&self, brett: &mut Schach) {
+        brett.set_piece_values(Arc::clone(&self.piece_values));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schach::assert_consistent;
+
+    const POSITIONS: [&str; 4] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "6k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1",
+        "8/p7/8/3k4/8/8/P7/K7 w - - 0 1",
+    ];
+
+    #[test]
+    fn custom_tables_stay_incremental() {
+        assert!(Arc::ptr_eq(&HAND_CRAFTED.piece_values, &PIECE_VALUES));
+        let mut weights = Weights::default();
+        weights.material[4] = 500;
+        // Bauern im Endspiel nur nach Reihe, aus Sicht von Weiß
+        weights.pst.eg[5] = std::array::from_fn(|square| 10 * (7 - square as i32 / 8));
+        let custom = HandCrafted::new(weights);
+        for fen in POSITIONS {
+            let mut brett = Schach::from_fen(fen).unwrap();
+            custom.prepare(&mut brett);
+            assert!(Arc::ptr_eq(brett.piece_values(), &custom.piece_values));
+            assert_consistent(&mut brett, 2);
+            // Ein Brett ohne prepare wird mit den eigenen Werten umgerechnet
+            assert_eq!(custom.evaluate(&brett), custom.evaluate(&Schach::from_fen(fen).unwrap()), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn round_trip_through_json() {
+        let json = Weights::default().to_json();
+        assert!(json.contains("\"doubled\": [-10, -25]"), "{}", json);
+        assert_eq!(Weights::from_json(&json), Ok(Weights::default()));
+        assert_eq!(Weights::from_json("{}"), Ok(Weights::default()));
+    }
+
+    #[test]
+    fn partial_files_change_the_evaluation() {
+        let weights = Weights::from_json(r#"{ "material": { "knight": 500 }, "mobility": { "bishop_pair": [0, 0] } }"#).unwrap();
+        assert_eq!(weights.material[4], 500);
+        assert_eq!(weights.mobility.bishop_pair, (0, 0));
+        assert_eq!(weights.pawns, PawnWeights::default());
+
+        let evaluator = HandCrafted::new(weights);
+        assert!(!Arc::ptr_eq(&evaluator.piece_values, &PIECE_VALUES));
+        // Weiß hat einen Springer mehr, der jetzt 195 mehr wert ist
+        let brett = Schach::from_fen("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(evaluator.evaluate(&brett).0 - HAND_CRAFTED.evaluate(&brett).0, 195);
+        assert_eq!(evaluator.evaluate(&Schach::new()).0, 0);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert_eq!(Weights::from_json(r#"{ "pawns": { "dubled": [1, 2] } }"#), Err(WeightsError::UnknownKey("pawns.dubled".to_string())));
+        assert_eq!(Weights::from_json(r#"{ "pawns": { "connected": [1, 2] } }"#), Err(WeightsError::InvalidValue {
+            key: "pawns.connected".to_string(),
+            expected: "a list of 8 integers".to_string(),
+        }));
+        assert_eq!(Weights::from_json(r#"{ "king_safety": { "open_file": 1.5 } }"#), Err(WeightsError::InvalidValue {
+            key: "king_safety.open_file".to_string(),
+            expected: "an integer".to_string(),
+        }));
+        assert!(matches!(Weights::from_json(r#"{ "pawns": 3 }"#), Err(WeightsError::InvalidValue { .. })));
+        assert!(matches!(Weights::from_json("{ \"pawns\": "), Err(WeightsError::Syntax(_))));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::schach::{ranks_ahead, squares, Color, Schach, FILE_A, LOOKUP_TABLE};
+
+// Bauernschild nach Abstand des eigenen Bauern vor dem König auf seiner und den Nachbarlinien,
+// Index 0 heißt kein Bauer mehr vor dem König
+const SHIELD: [i32; 8] = [-36, 0, -10, -22, -28, -32, -34, -36];
+// Zusätzlich, wenn auf der Linie überhaupt kein Bauer mehr steht
+const OPEN_FILE: i32 = -15;
+// Gegnerische Bauern, die auf den König zulaufen, nach Abstand. Direkt davor ist er blockiert
+const STORM: [i32; 8] = [0, -5, -30, -15, -6, 0, 0, 0];
+// Wert eines Angreifers der Königszone, mal Anzahl der angegriffenen Felder
+const KNIGHT_ATTACK: i32 = 20;
+const BISHOP_ATTACK: i32 = 20;
+const ROOK_ATTACK: i32 = 40;
+const QUEEN_ATTACK: i32 = 80;
+// Wie viel Prozent der Angriffe zählen, nach Anzahl der Angreifer. Ein einzelner Angreifer ist harmlos
+const ATTACK_WEIGHT: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KingSafetyWeights {
+    pub shield: [i32; 8],
+    pub open_file: i32,
+    pub storm: [i32; 8],
+    pub knight_attack: i32,
+    pub bishop_attack: i32,
+    pub rook_attack: i32,
+    pub queen_attack: i32,
+    pub attack_weight: [i32; 8],
+}
+
+impl Default for KingSafetyWeights {
+    fn default() -> Self {
+        KingSafetyWeights {
+            shield: SHIELD,
+            open_file: OPEN_FILE,
+            storm: STORM,
+            knight_attack: KNIGHT_ATTACK,
+            bishop_attack: BISHOP_ATTACK,
+            rook_attack: ROOK_ATTACK,
+            queen_attack: QUEEN_ATTACK,
+            attack_weight: ATTACK_WEIGHT,
+        }
+    }
+}
+
+// Angriffsfelder einer Figur von einem Feld aus, bei gegebener Besetzung
+type Attacks = fn(u64, u64) -> u64;
+
+// Königssicherheit als (Mittelspiel, Endspiel) aus Sicht von Weiß. Im Endspiel wird der König
+// selbst zur Angriffsfigur, dann zählen nur noch die Angriffe mit einem Viertel
+pub fn evaluate(brett: &Schach, weights: &KingSafetyWeights) -> (i32, i32) {
+    let (white_mg, white_eg) = evaluate_side(brett, &Color::White, weights);
+    let (black_mg, black_eg) = evaluate_side(brett, &Color::Black, weights);
+    (white_mg - black_mg, white_eg - black_eg)
+}
+
+fn evaluate_side(brett: &Schach, color: &Color, weights: &KingSafetyWeights) -> (i32, i32) {
+    let enemy = color.opposite();
+    let (king, _, _, _, _, own_pawns) = brett.bitboards(color);
+    let (_, queen, rooks, bishops, knights, enemy_pawns) = brett.bitboards(&enemy);
+    let square = king.trailing_zeros() as u64;
+    let (x, y) = (square % 8, square / 8);
+
+    let mut shelter = 0;
+    for file in x.saturating_sub(1)..=(x + 1).min(7) {
+        let ahead = FILE_A << file & ranks_ahead(y, color);
+        shelter += weights.shield[distance(own_pawns & ahead, y, color)];
+        shelter += weights.storm[distance(enemy_pawns & ahead, y, color)];
+        if (own_pawns | enemy_pawns) & FILE_A << file == 0 {
+            shelter += weights.open_file;
+        }
+    }
+
+    // Königszone: der König und alle Felder, die er betreten kann
+    let zone = king | LOOKUP_TABLE.get_king_moves(square);
+    let occupied = brett.occupied();
+    let mut attackers = 0;
+    let mut attack = 0;
+    let pieces: [(u64, i32, Attacks); 4] = [
+        (knights, weights.knight_attack, |from, _| LOOKUP_TABLE.get_knight_moves(from)),
+        (bishops, weights.bishop_attack, |from, occupied| LOOKUP_TABLE.get_bishop_moves(from, occupied)),
+        (rooks, weights.rook_attack, |from, occupied| LOOKUP_TABLE.get_rook_moves(from, occupied)),
+        (queen, weights.queen_attack, |from, occupied| LOOKUP_TABLE.get_queen_moves(from, occupied)),
+    ];
+    for (bitboard, value, attacks) in pieces {
+        for from in squares(bitboard) {
+            let attacks = attacks(from, occupied) & zone;
+            if attacks != 0 {
+                attackers += 1;
+                attack += value * attacks.count_ones() as i32;
+            }
+        }
+    }
+    let danger = attack * weights.attack_weight[attackers.min(7)] / 100;
+    (shelter - danger, -danger / 4)
+}
+
+// Abstand in Reihen zum nächsten Bauern vor dem König, 0 wenn es keinen gibt
+fn distance(pawns: u64, y: u64, color: &Color) -> usize {
+    if pawns == 0 {
+        return 0;
+    }
+    let nearest = match color {
+        Color::White => 63 - pawns.leading_zeros() as u64,
+        Color::Black => pawns.trailing_zeros() as u64,
+    };
+    (nearest / 8).abs_diff(y) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white_safety(fen: &str) -> (i32, i32) {
+        evaluate_side(&Schach::from_fen(fen).unwrap(), &Color::White, &KingSafetyWeights::default())
+    }
+
+    #[test]
+    fn intact_shield_is_better() {
+        let castled = white_safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let advanced = white_safety("6k1/5ppp/8/8/8/6P1/5P1P/6K1 w - - 0 1");
+        let open = white_safety("6k1/5p1p/8/8/8/8/5P1P/6K1 w - - 0 1");
+        assert_eq!(castled, (0, 0));
+        assert_eq!(advanced.0, SHIELD[2]);
+        assert_eq!(open.0, SHIELD[0] + OPEN_FILE);
+    }
+
+    #[test]
+    fn storming_pawns() {
+        let storm = white_safety("6k1/5p1p/8/8/8/6p1/5PPP/6K1 w - - 0 1");
+        assert_eq!(storm.0, STORM[2]);
+    }
+
+    #[test]
+    fn attackers_of_the_king_zone() {
+        // Springer auf g4 und Dame auf h4 greifen die Felder um den weißen König an
+        let single = white_safety("6k1/5ppp/8/8/6n1/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(single, (0, 0));
+        let double = white_safety("6k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1");
+        assert!(double.0 < -50, "{:?}", double);
+        assert!(double.1 > double.0);
+        // Der schwarze König steht sicher, die Summe aus Sicht von Weiß ist nur der weiße Teil
+        let brett = Schach::from_fen("6k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(evaluate(&brett, &KingSafetyWeights::default()), double);
+    }
+}
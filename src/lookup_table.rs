@@ -1,186 +1,190 @@
-use std::collections::HashMap;
+use crate::schach::Color;
 
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+// Felder werden wie in Schach als x + 8 * y gezählt, y = 0 ist die achte Reihe
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [(2, 1), (2, -1), (1, 2), (-2, 1), (-2, -1), (-1, 2), (1, -2), (-1, -2)];
+const KING_OFFSETS: [(i32, i32); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+// Die Magics wurden einmalig durch Ausprobieren zufälliger, dünn besetzter Zahlen gefunden,
+// bis jede Belegung der Maske auf einen eigenen Index abgebildet wurde
+const ROOK_MAGICS: [u64; 64] = [
+    0x0080006040009380, 0x4240002001100240, 0x2080200008100080, 0x9180280080841000,
+    0x0900110002040800, 0x0380020013801400, 0x0400008110084402, 0x0200040840208702,
+    0x0006800880400020, 0x4101002110400080, 0x0100801000200082, 0x0014801005800800,
+    0x0542000410082200, 0x4112000802001004, 0x0414001022440108, 0x2822000082310044,
+    0x0080004020004004, 0x0050004040002000, 0x014602004152a080, 0x0000a30010010008,
+    0x0000808008000402, 0x0004808002000401, 0x0200040001100802, 0x20002a002ac08304,
+    0x00c0802080004000, 0xa040100020080020, 0x2040100080200084, 0x0000100280080080,
+    0x8148000404002040, 0x0801000300040008, 0x0000100400420881, 0x0100010200008044,
+    0x0000804000800020, 0x4010002000400042, 0x4085001841002000, 0x0400801000800800,
+    0x1988010005001008, 0x0002000400800280, 0x4802000102000408, 0x020800a506000444,
+    0x0000400080008021, 0x0070002000404001, 0x1000200041010010, 0x8080100008008080,
+    0x4802001088060020, 0x0002000804010100, 0x02aa681009840006, 0x800c004084020001,
+    0x0002104024810200, 0x0280210246028200, 0x3280102000410100, 0x0100080080100280,
+    0x0524008801800480, 0x0411002400080300, 0x0044800200010080, 0x4301000080420100,
+    0x4040241900800041, 0x0004400411038421, 0x0200084020001101, 0x1001000410002009,
+    0x0402004560085002, 0x000d000400020801, 0x0042020801009004, 0x88020c0021c70082,
+];
+
+const BISHOP_MAGICS: [u64; 64] = [
+    0x0082205202004101, 0x0004084816408cc0, 0x008800a408808411, 0x8208086101440004,
+    0xc022021100071100, 0x0102021004024810, 0xc4008084202002c2, 0x0840805802100204,
+    0x0400082008020060, 0xa54202100e409102, 0x200108480d003004, 0x8c20480681000080,
+    0x6080040b08008002, 0x14000a0814042800, 0x0600508801082020, 0x4000020080849004,
+    0x0008004042084204, 0x80200012080a4288, 0x800880040c001200, 0x0042282802004030,
+    0x8021000820082200, 0x100a200e00842000, 0x0200540b0c100400, 0x8800400421086800,
+    0x000249c040080810, 0x0082020160080243, 0x800a440818080210, 0x0002080004004088,
+    0x1804082084002000, 0x000a4a000b030100, 0x8104040142825144, 0x0611102007049800,
+    0x0804104400492000, 0x1094022000c30401, 0x001c060440080045, 0xa000840400080210,
+    0x1224024200240090, 0x2010020088021008, 0x01084a0044808840, 0x0048110843410040,
+    0x0042100406402020, 0x1084010462011000, 0x0000202928001000, 0x8008808420200400,
+    0x071a088100400400, 0x2040040802880210, 0x1013020802000f00, 0xc910010101048021,
+    0x0102010148400501, 0x10020100821006c0, 0x0006020211440448, 0x0381000020880084,
+    0x400848a893040000, 0x402041a801050000, 0x000411021204000c, 0x0004440404002040,
+    0x0020190088200800, 0x2000803508021041, 0x2800801022311000, 0x0041205010842404,
+    0x0000c00084050404, 0x1004002004307080, 0x6004104450040068, 0x0402021044008080,
+];
 
 #[derive(Clone)]
-pub struct LookupTable {
-    rook_move_mask: Vec<u64>,
-    rook_moves1: Vec<u64>,
-    rook_moves2: Vec<u64>,
-    rook_moves3: Vec<u64>,
-    rook_moves4: Vec<u64>,
-    rook_moves5: Vec<u64>,
-    rook_moves6: Vec<u64>,
-    rook_moves7: Vec<u64>,
-    rook_moves8: Vec<u64>,
-    
+struct Magic {
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
 }
 
+impl Magic {
+    fn index(&self, all_pieces: u64) -> usize {
+        self.offset + ((all_pieces & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+#[derive(Clone)]
+pub struct LookupTable {
+    rook_magics: Vec<Magic>,
+    bishop_magics: Vec<Magic>,
+    rook_moves: Vec<u64>,
+    bishop_moves: Vec<u64>,
+    knight_moves: Vec<u64>,
+    king_moves: Vec<u64>,
+    white_pawn_attacks: Vec<u64>,
+    black_pawn_attacks: Vec<u64>,
+}
 
 impl LookupTable {
     pub fn new() -> Self {
-        // contains all possible moves for the rook. The key is the bitboard with the rook's position, the value is the bitboard of all possible moves
-        struct BitboardIterator {
-            stack: Vec<(u64, usize)>,
-        }
-        
-        impl BitboardIterator {
-            fn new(bitboard: u64) -> Self {
-                BitboardIterator {
-                    stack: vec![(bitboard, 0)],
-                }
-            }
-        }
-        
-        impl Iterator for BitboardIterator {
-            type Item = u64;
-            fn next(&mut self) -> Option<Self::Item> {
-                while let Some((bitboard, start)) = self.stack.pop() {
-                    for i in start..64 {
-                        if (bitboard & (1 << i)) != 0 {
-                            let new_bitboard = bitboard & !(1 << i);
-                            self.stack.push((bitboard, i + 1));
-                            self.stack.push((new_bitboard, i + 1));
-                            return Some(bitboard);
-                        }
-                    }
-                }
-                None
-            }
+        let (rook_magics, rook_moves) = Self::init_sliding(&ROOK_DIRECTIONS, &ROOK_MAGICS);
+        let (bishop_magics, bishop_moves) = Self::init_sliding(&BISHOP_DIRECTIONS, &BISHOP_MAGICS);
+        Self {
+            rook_magics,
+            bishop_magics,
+            rook_moves,
+            bishop_moves,
+            knight_moves: (0..64).map(|square| step_attacks(square, &KNIGHT_OFFSETS)).collect(),
+            king_moves: (0..64).map(|square| step_attacks(square, &KING_OFFSETS)).collect(),
+            white_pawn_attacks: (0..64).map(|square| step_attacks(square, &[(-1, -1), (1, -1)])).collect(),
+            black_pawn_attacks: (0..64).map(|square| step_attacks(square, &[(-1, 1), (1, 1)])).collect(),
         }
+    }
 
-        let mut rook_move_mask: Vec<u64> = Vec::new();
-        // iterate through all possible rook positions
+    fn init_sliding(directions: &[(i32, i32); 4], magic_numbers: &[u64; 64]) -> (Vec<Magic>, Vec<u64>) {
+        let mut magics = Vec::with_capacity(64);
+        let mut moves = Vec::new();
         for square in 0..64 {
-            let mut moves: u64 = 0;
-            for target in 0..64 {
-                if (target / 8 == square / 8) || (target % 8 == square % 8) && target != square {
-                    moves |= 1 << target;
+            let mask = relevant_mask(square, directions);
+            let magic = Magic {
+                mask,
+                magic: magic_numbers[square as usize],
+                shift: 64 - mask.count_ones(),
+                offset: moves.len(),
+            };
+            moves.resize(moves.len() + (1 << mask.count_ones()), 0);
+
+            // Alle Teilmengen der Maske durchgehen (Carry-Rippler)
+            let mut subset: u64 = 0;
+            loop {
+                moves[magic.index(subset)] = sliding_attacks(square, subset, directions);
+                subset = subset.wrapping_sub(mask) & mask;
+                if subset == 0 {
+                    break;
                 }
             }
-            rook_move_mask.push(moves);
+            magics.push(magic);
         }
-        
-        let mut rook_moves: HashMap<u64, u64> = HashMap::new();
-        
-        let filter:u64 = 0b00000000_01111110_01111110_01111110_01111110_01111110_01111110_00000000;
-        let dirs = [(1, 0), (-1, 0), (0, 1), (0, -1)];
-        for x in 0..8 {
-            for y in 0..8 {
-                let iterator = BitboardIterator::new(rook_move_mask[x * 8 + y]);
-                for bitboard in  iterator {   
-                    let mut moves: u64 = 0;
-                    for dir in dirs.iter() {
-                        let mut i = x as i32 + dir.0;
-                        let mut j = y as i32 + dir.1;
-                        while i >= 0 && i < 8 && j >= 0 && j < 8 {
-                            moves |= 1 << (i * 8 + j);
-                            if bitboard & (1 << (i * 8 + j)) != 0 {
-                                break;
-                            }
-                            i += dir.0;
-                            j += dir.1;
-                        }
-                    }
-                    rook_moves.insert(bitboard & filter, moves);
-                }
-            }
-        }    
-
-        // Magic number generation
-        // (0..16).into_par_iter().for_each(|a| {
-        //     let mut best_factor;
-        //     let mut trash_bits = 41;
-        //     'outer: loop {
-        //         let factor = fastrand::u64(..);
-        //         let mut indexset: HashSet<u64> = HashSet::new();
-        //         for (k,_) in rook_moves.iter() {
-        //             if k.count_zeros() % 8 != a % 8 {
-        //                 continue;
-        //             }
-        //             if indexset.contains(&((k * &factor) >> trash_bits as u64)) {
-        //                 continue 'outer;
-        //             } else {
-        //                 indexset.insert((k * &factor) >> trash_bits as u64);
-        //             }
-        //         }
-        //         best_factor = factor;
-        //         println!("a mod 8: {}, factor: {}, trash_bits: {}", a % 8, best_factor, trash_bits);
-        //         trash_bits += 1;
-        //         if trash_bits > 60 {
-        //             break;
-        //         }
-        //     }
-        // });
-
-
-        let mut rook_moves1 = vec![0; 2usize.pow(20)];
-        let mut rook_moves2 = vec![0; 2usize.pow(22)];
-        let mut rook_moves3 = vec![0; 2usize.pow(23)];
-        let mut rook_moves4 = vec![0; 2usize.pow(23)];
-        let mut rook_moves5 = vec![0; 2usize.pow(22)];
-        let mut rook_moves6 = vec![0; 2usize.pow(19)];
-        let mut rook_moves7 = vec![0; 2usize.pow(15)];
-        let mut rook_moves8 = vec![0; 2usize.pow(17)];
-
-        let magic_numbers = [(8458573397933691365u64, 44u64), (8757177435623954480, 42), (9916889599927644802, 41), (15590192793224235728, 41), (5672935802224073713, 42), (7039174516308744092, 45), (71762459037376191, 49), (15625145245058179273, 47)];
-        for (k,v) in rook_moves.iter() {
-            let (factor, trash_bits) = magic_numbers[(k.count_zeros() % 8) as usize];
-            let index = ((k * factor) >> trash_bits) as usize;
-            match k.count_zeros() % 8 {
-                0 => rook_moves1[index] = *v,
-                1 => rook_moves2[index] = *v,
-                2 => rook_moves3[index] = *v,
-                3 => rook_moves4[index] = *v,
-                4 => rook_moves5[index] = *v,
-                5 => rook_moves6[index] = *v,
-                6 => rook_moves7[index] = *v,
-                7 => rook_moves8[index] = *v,
-                _ => panic!("Index out of bounds"),
-            }
-        }    
-        print!("Hai");
-        Self {
-            rook_move_mask,
-            rook_moves1,
-            rook_moves2,
-            rook_moves3,
-            rook_moves4,
-            rook_moves5,
-            rook_moves6,
-            rook_moves7,
-            rook_moves8,
+        (magics, moves)
+    }
+
+    pub fn get_rook_moves(&self, square: u64, all_pieces: u64) -> u64 {
+        self.rook_moves[self.rook_magics[square as usize].index(all_pieces)]
+    }
+
+    pub fn get_bishop_moves(&self, square: u64, all_pieces: u64) -> u64 {
+        self.bishop_moves[self.bishop_magics[square as usize].index(all_pieces)]
+    }
+
+    pub fn get_queen_moves(&self, square: u64, all_pieces: u64) -> u64 {
+        self.get_rook_moves(square, all_pieces) | self.get_bishop_moves(square, all_pieces)
+    }
+
+    pub fn get_knight_moves(&self, square: u64) -> u64 {
+        self.knight_moves[square as usize]
+    }
+
+    pub fn get_king_moves(&self, square: u64) -> u64 {
+        self.king_moves[square as usize]
+    }
+
+    // Felder, die ein Bauer der Farbe c auf square angreift
+    pub fn get_pawn_attacks(&self, square: u64, c: &Color) -> u64 {
+        match c {
+            Color::White => self.white_pawn_attacks[square as usize],
+            Color::Black => self.black_pawn_attacks[square as usize],
+        }
+    }
+}
+
+fn step_attacks(square: u64, offsets: &[(i32, i32)]) -> u64 {
+    let (x, y) = ((square % 8) as i32, (square / 8) as i32);
+    let mut result = 0;
+    for (d_x, d_y) in offsets {
+        let (a, b) = (x + d_x, y + d_y);
+        if (0..8).contains(&a) && (0..8).contains(&b) {
+            result |= 1 << (a + 8 * b);
         }
     }
+    result
+}
 
-    pub fn get_rook_moves(&self, position_map:u64, all_pieces: u64) -> u64 {
-        let filter:u64 = 0b00000000_01111110_01111110_01111110_01111110_01111110_01111110_00000000;
-        let relevant_pieces = all_pieces & self.get_rook_move_mask(63 - position_map.leading_zeros() as u64) & filter;
-        let magic_index = relevant_pieces.count_zeros() % 8;
-        let (magic_number, magic_shift) = match magic_index {
-            0 => (8458573397933691365u64, 44u64),
-            1 => (8757177435623954480, 42),
-            2 => (9916889599927644802, 41),
-            3 => (15590192793224235728, 41),
-            4 => (5672935802224073713, 42),
-            5 => (7039174516308744092, 45),
-            6 => (71762459037376191, 49),
-            _ => (15625145245058179273, 47),
-        };
-        let index = ((relevant_pieces * magic_number) >> magic_shift) as usize;
-        match magic_index {
-            0 => self.rook_moves1[index],
-            1 => self.rook_moves2[index],
-            2 => self.rook_moves3[index],
-            3 => self.rook_moves4[index],
-            4 => self.rook_moves5[index],
-            5 => self.rook_moves6[index],
-            6 => self.rook_moves7[index],
-            _ => self.rook_moves8[index],
+// Zugfelder einer Langschrittfigur, die Strahlen enden auf dem ersten besetzten Feld
+fn sliding_attacks(square: u64, all_pieces: u64, directions: &[(i32, i32); 4]) -> u64 {
+    let mut result = 0;
+    for (d_x, d_y) in directions {
+        let mut a = (square % 8) as i32 + d_x;
+        let mut b = (square / 8) as i32 + d_y;
+        while (0..8).contains(&a) && (0..8).contains(&b) {
+            result |= 1 << (a + 8 * b);
+            if all_pieces >> (a + 8 * b) & 1 == 1 {
+                break;
+            }
+            a += d_x;
+            b += d_y;
         }
     }
+    result
+}
 
-    pub fn get_rook_move_mask(&self, square: u64) -> u64 {
-        self.rook_move_mask[63 - square.leading_zeros() as usize]
+// Nur Figuren vor dem Brettrand können einen Strahl blockieren
+fn relevant_mask(square: u64, directions: &[(i32, i32); 4]) -> u64 {
+    let mut result = 0;
+    for (d_x, d_y) in directions {
+        let mut a = (square % 8) as i32 + d_x;
+        let mut b = (square / 8) as i32 + d_y;
+        while (0..8).contains(&(a + d_x)) && (0..8).contains(&(b + d_y)) {
+            result |= 1 << (a + 8 * b);
+            a += d_x;
+            b += d_y;
+        }
     }
+    result
 }
@@ -1,6 +1,7 @@
 extern crate sdl2;
 
 use schach::Schach;
+use moves::Move;
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
 use sdl2::event::Event;
@@ -12,14 +13,56 @@ use std::time::SystemTime;
 use std::time::Duration;
 use std::thread;
 use std::sync::mpsc;
+use std::sync::Arc;
+use transposition_table::TranspositionTable;
+use search::{SearchHandle, SearchInfo, SearchLimits, SearchOptions, SearchResult};
+use evaluation::{HandCrafted, Weights};
 pub mod texture_manager;
 pub mod schach;
 pub mod lookup_table;
+pub mod moves;
+pub mod perft;
+pub mod zobrist;
+pub mod transposition_table;
+pub mod search;
+pub mod movepick;
+pub mod score;
+pub mod pst;
+pub mod pawns;
+pub mod king_safety;
+pub mod mobility;
+pub mod uci;
+pub mod evaluation;
 
 const SQUARE_SIZE:u32 = 100;
 
 pub fn main() -> Result<(), String> {
-    rayon::ThreadPoolBuilder::new().num_threads(18).build_global().unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 2 && args[1] == "perft" {
+        return run_perft(&args[2], &args[3..]);
+    }
+    if args.len() > 1 && args[1] == "weights" {
+        print!("{}", Weights::default().to_json());
+        return Ok(());
+    }
+
+    // Bewertungsgewichte mit --weights <Datei>, eine Vorlage gibt "weights" aus
+    let weights = match args.iter().position(|a| a == "--weights").and_then(|i| args.get(i + 1)) {
+        Some(path) => Weights::load(path).map_err(|e| e.to_string())?,
+        None => Weights::default(),
+    };
+    let evaluator = Arc::new(HandCrafted::new(weights));
+    if args.len() > 1 && args[1] == "uci" {
+        uci::run(evaluator);
+        return Ok(());
+    }
+
+    // Suchthreads mit --threads N, sonst einer pro Kern
+    let threads = args.iter().position(|a| a == "--threads")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|n| n.parse().ok())
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let search_options = SearchOptions { random_tie_break: true, threads, ..Default::default() };
 
     let mut brett = Schach::new();
     
@@ -57,42 +100,68 @@ pub fn main() -> Result<(), String> {
     let mut arrows: Vec<(i32, i32, i32, i32)> = Vec::new();
     let mut start_pos_right: Option<(i32, i32)> = None; 
     let mut start_pos_left: Option<(i32, i32)> = None; 
+    let mut promotion_choice: Vec<Move> = Vec::new();
+    let mut draw_agreed = false;
+    let mut played_moves: Vec<(Move, schach::Undo)> = Vec::new();
     
-    let mut calulation_running = false;
     let mut calculation_end = SystemTime::now();
-    let mut rx: mpsc::Receiver<(u64, u64, u64, u64)> = mpsc::channel().1;
-    let mut tx: mpsc::Sender<(u64, u64, u64, u64)>;
+    // Laufende Suche der Engine, verwerfen bricht sie ab
+    let mut search: Option<SearchHandle> = None;
+    // Zwischenstände der Suche, mit dem Hash der Stellung, damit veraltete Meldungen erkannt werden
+    let (info_tx, info_rx) = mpsc::channel::<(u64, SearchInfo)>();
+    let mut analysis: Option<(u64, SearchInfo)> = None;
+    // Bleibt über die ganze Partie erhalten
+    let tt = Arc::new(TranspositionTable::new(64));
 
     'running: loop {
 
         canvas.clear();
 
-        match brett.get_outcome() {
+        let outcome = match draw_agreed {
+            true => schach::Outcome::Draw(schach::DrawReason::Agreement),
+            false => brett.get_outcome(),
+        };
+        for (key, info) in info_rx.try_iter() {
+            if key == brett.hash() {
+                canvas.window_mut().set_title(&analysis_title(&brett, &info)).ok();
+                analysis = Some((key, info));
+            }
+        }
+
+        match outcome {
             schach::Outcome::None => {
-                if let Ok((a,b,c,d)) = rx.try_recv() {
-                    calculation_end = SystemTime::now();
-                    calulation_running = false;
-                    selected_squares.clear();
-                    arrows.clear();
-                    selected_squares.insert((a as i32,b as i32)); 
-                    selected_squares.insert((c as i32,d as i32));
-                    brett.move_piece(a, b, c, d);
-                } else if !calulation_running {
-                    calulation_running = true;
-                    (tx, rx) = std::sync::mpsc::channel();
-                    let brett_clone = brett.clone();
-                    thread::spawn(move || {
-                        let (a,b,c,d) = brett_clone.best_move(3, SystemTime::now()); 
-                        tx.send((a,b,c,d)).unwrap();
-                    });
+                if search.as_ref().is_some_and(|s| s.is_finished()) {
+                    let result = search.take().unwrap().wait();
+                    print_search_result(&brett, &result);
+                    if let Some(m) = result.best_move {
+                        let (a,b) = m.from_xy();
+                        let (c,d) = m.to_xy();
+                        calculation_end = SystemTime::now();
+                        selected_squares.clear();
+                        arrows.clear();
+                        promotion_choice.clear();
+                        selected_squares.insert((a as i32,b as i32)); 
+                        selected_squares.insert((c as i32,d as i32));
+                        played_moves.push((m, brett.make_move(m)));
+                    }
+                } else if search.is_none() {
+                    let key = brett.hash();
+                    let info_tx = info_tx.clone();
+                    search = Some(SearchHandle::start_with(brett.clone(), SearchLimits::movetime(Duration::from_secs(1)), search_options.clone(), Arc::clone(&tt), Arc::clone(&evaluator), move |info| {
+                        info_tx.send((key, info.clone())).ok();
+                    }, |_| ()));
                 }
             },
             _ => {
                 if calculation_end.elapsed().unwrap().as_secs() > 5 {
                     selected_squares.clear();
                     arrows.clear();
-                    print_outcome(&brett);
+                    print_outcome(&brett, &outcome, &evaluator);
+                    search = None;
                     brett = Schach::new();
+                    tt.clear();
+                    played_moves.clear();
+                    draw_agreed = false;
                 }
             },
         }
@@ -105,9 +174,31 @@ pub fn main() -> Result<(), String> {
                     break 'running;
                 },
                 Event::KeyDown { keycode: Some(Keycode::R), .. } => {
+                    search = None;
                     brett = schach::Schach::new();
+                    tt.clear();
+                    played_moves.clear();
                     selected_squares.clear();
                     arrows.clear();
+                    promotion_choice.clear();
+                    draw_agreed = false;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                    // Letzten Zug zurücknehmen, eine laufende Berechnung wird verworfen
+                    if let Some((m, undo)) = played_moves.pop() {
+                        search = None;
+                        brett.unmake_move(m, undo);
+                        draw_agreed = false;
+                        selected_squares.clear();
+                        arrows.clear();
+                        promotion_choice.clear();
+                        active_piece = None;
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::D), .. } if !draw_agreed && brett.get_outcome() == schach::Outcome::None => {
+                    // Remis vereinbaren
+                    draw_agreed = true;
+                    calculation_end = SystemTime::now();
                 },
                 Event::MouseButtonDown { mouse_btn, x, y, .. } => {
                     match mouse_btn {
@@ -143,16 +234,38 @@ pub fn main() -> Result<(), String> {
                             }
                             start_pos_right = None;
                         }
+                        MouseButton::Left if !promotion_choice.is_empty() => {
+                            // Auswahl der Umwandlungsfigur
+                            let (c,d) = promotion_choice[0].to_xy();
+                            let square = (x / SQUARE_SIZE as i32, y / SQUARE_SIZE as i32);
+                            if let Some((_, _, p)) = promotion_options(c as i32, d as i32).into_iter().find(|(i, j, _)| (*i, *j) == square) {
+                                if let Some(m) = promotion_choice.iter().find(|m| m.promotion() == Some(p.clone())) {
+                                    search = None;
+                                    played_moves.push((*m, brett.make_move(*m)));
+                                    print_outcome(&brett, &brett.get_outcome(), &evaluator);
+                                }
+                            }
+                            promotion_choice.clear();
+                            selected_squares.clear();
+                            active_piece = None;
+                            start_pos_left = None;
+                        }
                         MouseButton::Left => {
                             if start_pos_left == Some((x / SQUARE_SIZE as i32, y / SQUARE_SIZE as i32)) {
-                                selected_squares = brett.get_legal_moves((x as u32 / SQUARE_SIZE) as u64, (y as u32 / SQUARE_SIZE) as u64, 1);
+                                selected_squares = brett.get_legal_moves((x as u32 / SQUARE_SIZE) as u64, (y as u32 / SQUARE_SIZE) as u64).iter().map(|m| (m.to_xy().0 as i32, m.to_xy().1 as i32)).collect();
                                 if !active_piece.is_none() && active_piece != start_pos_left {
                                     let (c,d) = start_pos_left.unwrap();
                                     let a = active_piece.unwrap().0;
                                     let b = active_piece.unwrap().1;
-                                    if brett.get_legal_moves(a as u64, b as u64, 1).contains(&(c,d)) {
-                                        brett.move_piece(a as u64, b as u64, c as u64, d as u64);
-                                        print_outcome(&brett);
+                                    let moves: Vec<Move> = brett.get_legal_moves(a as u64, b as u64).into_iter().filter(|m| m.to_xy() == (c as u64, d as u64)).collect();
+                                    if moves.len() > 1 {
+                                        promotion_choice = moves;
+                                        selected_squares.clear();
+                                        active_piece = None;
+                                    } else if let Some(m) = moves.first() {
+                                        search = None;
+                                        played_moves.push((*m, brett.make_move(*m)));
+                                        print_outcome(&brett, &brett.get_outcome(), &evaluator);
                                         selected_squares.clear();
                                         active_piece = None;
                                     }
@@ -165,9 +278,14 @@ pub fn main() -> Result<(), String> {
                                 let (a,b) = start_pos_left.unwrap();
                                 let c = x / SQUARE_SIZE as i32;
                                 let d = y / SQUARE_SIZE as i32;
-                                if brett.get_legal_moves(a as u64, b as u64, 1).contains(&(c,d)) {
-                                    brett.move_piece(a as u64, b as u64, c as u64, d as u64);
-                                    print_outcome(&brett);
+                                let moves: Vec<Move> = brett.get_legal_moves(a as u64, b as u64).into_iter().filter(|m| m.to_xy() == (c as u64, d as u64)).collect();
+                                if moves.len() > 1 {
+                                    promotion_choice = moves;
+                                    selected_squares.clear();
+                                } else if let Some(m) = moves.first() {
+                                    search = None;
+                                    played_moves.push((*m, brett.make_move(*m)));
+                                    print_outcome(&brett, &brett.get_outcome(), &evaluator);
                                     selected_squares.clear();
                                 }
                             }
@@ -205,20 +323,7 @@ pub fn main() -> Result<(), String> {
 
         // Figuren
         for (c,p,i,j) in  brett.get_positions() {
-            let texture_name = match (p,c) {
-                (schach::Piece::King, schach::Color::White) => "img/white-king.png",
-                (schach::Piece::King, schach::Color::Black) => "img/black-king.png",
-                (schach::Piece::Queen, schach::Color::White) => "img/white-queen.png",
-                (schach::Piece::Queen, schach::Color::Black) => "img/black-queen.png",
-                (schach::Piece::Rook, schach::Color::White) => "img/white-rook.png",
-                (schach::Piece::Rook, schach::Color::Black) => "img/black-rook.png",
-                (schach::Piece::Bishop, schach::Color::White) => "img/white-bishop.png",
-                (schach::Piece::Bishop, schach::Color::Black) => "img/black-bishop.png",
-                (schach::Piece::Knight, schach::Color::White) => "img/white-knight.png",
-                (schach::Piece::Knight, schach::Color::Black) => "img/black-knight.png",
-                (schach::Piece::Pawn, schach::Color::White) => "img/white-pawn.png",
-                (schach::Piece::Pawn, schach::Color::Black) => "img/black-pawn.png",
-            };
+            let texture_name = texture_name(&p, &c);
             let img_size = 128;
             let texture = tex_man.load(&texture_name)?;
             let src = Rect::new(0,0,img_size,img_size);
@@ -238,12 +343,37 @@ pub fn main() -> Result<(), String> {
             )?;                  
         }
 
+
+        // Umwandlungsauswahl
+        if let Some(m) = promotion_choice.first() {
+            let (c,d) = m.to_xy();
+            for (i,j,p) in promotion_options(c as i32, d as i32) {
+                let dest = Rect::new(i * SQUARE_SIZE as i32, j * SQUARE_SIZE as i32, SQUARE_SIZE, SQUARE_SIZE);
+                canvas.set_draw_color(Color::RGB(200, 200, 200));
+                canvas.fill_rect(dest)?;
+                let texture = tex_man.load(texture_name(&p, &brett.active_player))?;
+                canvas.copy(&texture, None, dest)?;
+            }
+        }
         
         // Pfeile
         for (start_x, start_y, end_x, end_y) in &arrows {
             let start = (start_x * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2, start_y * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2);
             let end = (end_x * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2, end_y * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2);
-            draw_arrow(&mut canvas, start, end);
+            draw_arrow(&mut canvas, start, end, Color::RGB(0, 255, 0));
+        }
+
+        // Laufende Analyse: bester Zug in Blau, erwartete Antwort in Orange
+        if let Some((key, info)) = &analysis {
+            if *key == brett.hash() {
+                for (m, color) in info.pv.iter().zip([Color::RGB(50, 120, 255), Color::RGB(255, 140, 0)]) {
+                    let (a, b) = m.from_xy();
+                    let (c, d) = m.to_xy();
+                    let start = (a as i32 * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2, b as i32 * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2);
+                    let end = (c as i32 * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2, d as i32 * SQUARE_SIZE as i32 + SQUARE_SIZE as i32 / 2);
+                    draw_arrow(&mut canvas, start, end, color);
+                }
+            }
         }
         
         canvas.present();
@@ -252,20 +382,92 @@ pub fn main() -> Result<(), String> {
     Ok(())
 }
 
-fn print_outcome(brett: &schach::Schach) {
-    match brett.get_outcome() {
+// Aufruf: schach perft <tiefe> [fen]
+fn run_perft(depth: &str, fen: &[String]) -> Result<(), String> {
+    let depth: u32 = depth.parse().map_err(|_| format!("invalid depth '{}'", depth))?;
+    let mut brett = match fen.is_empty() {
+        true => Schach::new(),
+        false => Schach::from_fen(&fen.join(" ")).map_err(|e| e.to_string())?,
+    };
+    let start = SystemTime::now();
+    let mut total = 0;
+    for (m, nodes) in brett.perft_divide(depth) {
+        println!("{}: {}", m, nodes);
+        total += nodes;
+    }
+    println!();
+    println!("Nodes searched: {}", total);
+    println!("Time: {:?}", start.elapsed().unwrap());
+    Ok(())
+}
+
+fn print_outcome(brett: &schach::Schach, outcome: &schach::Outcome, evaluator: &HandCrafted) {
+    match outcome {
         schach::Outcome::Checkmate(schach::Color::White) => println!("Weiss gewinnt"),
         schach::Outcome::Checkmate(schach::Color::Black) => println!("Schwarz gewinnt"),
-        schach::Outcome::Stalemate => println!("Stalemate"),
-        schach::Outcome::None => println!("Evaluation: {}", brett.eval_position()),
+        schach::Outcome::Draw(schach::DrawReason::Stalemate) => println!("Remis durch Patt"),
+        schach::Outcome::Draw(schach::DrawReason::FiftyMoveRule) => println!("Remis durch die 50-Züge-Regel"),
+        schach::Outcome::Draw(schach::DrawReason::InsufficientMaterial) => println!("Remis durch ungenügendes Material"),
+        schach::Outcome::Draw(schach::DrawReason::Repetition) => println!("Remis durch dreifache Stellungswiederholung"),
+        schach::Outcome::Draw(schach::DrawReason::Agreement) => println!("Remis durch Vereinbarung"),
+        schach::Outcome::None => println!("Evaluation: {}", brett.eval_position_with(evaluator)),
+    }
+}
+
+// Bewertung aus Sicht von Weiß
+fn print_search_result(brett: &schach::Schach, result: &SearchResult) {
+    let eval = match brett.active_player {
+        schach::Color::White => result.score,
+        schach::Color::Black => -result.score,
+    };
+    let pv: Vec<String> = result.pv.iter().map(|m| m.to_string()).collect();
+    println!("tiefe: {}, eval: {}, knoten: {}, time: {:?}, pv: {}", result.depth, eval, result.nodes, result.elapsed, pv.join(" "));
+    println!("nachsuchen: {}", result.stats);
+}
+
+// Fenstertitel mit dem Stand der laufenden Suche, Bewertung aus Sicht von Weiß
+fn analysis_title(brett: &schach::Schach, info: &SearchInfo) -> String {
+    let eval = match brett.active_player {
+        schach::Color::White => info.score,
+        schach::Color::Black => -info.score,
+    };
+    let pv: Vec<String> = info.pv.iter().take(6).map(|m| m.to_string()).collect();
+    format!("Schach - tiefe {}/{}, eval {}, {} knoten/s, hash {}‰, pv {}", info.depth, info.seldepth, eval, info.nps, info.hashfull, pv.join(" "))
+}
+
+fn texture_name(p: &schach::Piece, c: &schach::Color) -> &'static str {
+    match (p,c) {
+        (schach::Piece::King, schach::Color::White) => "img/white-king.png",
+        (schach::Piece::King, schach::Color::Black) => "img/black-king.png",
+        (schach::Piece::Queen, schach::Color::White) => "img/white-queen.png",
+        (schach::Piece::Queen, schach::Color::Black) => "img/black-queen.png",
+        (schach::Piece::Rook, schach::Color::White) => "img/white-rook.png",
+        (schach::Piece::Rook, schach::Color::Black) => "img/black-rook.png",
+        (schach::Piece::Bishop, schach::Color::White) => "img/white-bishop.png",
+        (schach::Piece::Bishop, schach::Color::Black) => "img/black-bishop.png",
+        (schach::Piece::Knight, schach::Color::White) => "img/white-knight.png",
+        (schach::Piece::Knight, schach::Color::Black) => "img/black-knight.png",
+        (schach::Piece::Pawn, schach::Color::White) => "img/white-pawn.png",
+        (schach::Piece::Pawn, schach::Color::Black) => "img/black-pawn.png",
     }
 }
 
-fn draw_arrow(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, start: (i32, i32), end: (i32, i32)) {
+// Die wählbaren Figuren werden vom Umwandlungsfeld aus Richtung Brettmitte angezeigt
+fn promotion_options(x: i32, y: i32) -> [(i32, i32, schach::Piece); 4] {
+    let direction = if y == 0 { 1 } else { -1 };
+    [
+        (x, y, schach::Piece::Queen),
+        (x, y + direction, schach::Piece::Knight),
+        (x, y + 2 * direction, schach::Piece::Rook),
+        (x, y + 3 * direction, schach::Piece::Bishop),
+    ]
+}
+
+fn draw_arrow(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, start: (i32, i32), end: (i32, i32), color: Color) {
     let (start_x, start_y) = start;
     let (end_x, end_y) = end;
 
-    canvas.set_draw_color(Color::RGB(0, 255, 0));
+    canvas.set_draw_color(color);
     canvas.draw_line(start, end).unwrap();
 
     let dir_x = end_x - start_x;
@@ -0,0 +1,182 @@
+use crate::pawns::pawn_attacks;
+use crate::schach::{adjacent_files, ranks_ahead, relative_rank, squares, Color, Schach, FILE_A, LOOKUP_TABLE};
+
+// (übliche Anzahl sicherer Zugfelder, Mittelspiel, Endspiel): Bonus pro Feld darüber, Abzug pro Feld darunter
+const KNIGHT_MOBILITY: (i32, i32, i32) = (4, 4, 4);
+const BISHOP_MOBILITY: (i32, i32, i32) = (6, 5, 5);
+const ROOK_MOBILITY: (i32, i32, i32) = (7, 2, 4);
+const QUEEN_MOBILITY: (i32, i32, i32) = (14, 1, 2);
+
+const ROOK_OPEN_FILE: (i32, i32) = (25, 10);
+const ROOK_SEMI_OPEN_FILE: (i32, i32) = (12, 6);
+const ROOK_ON_SEVENTH: (i32, i32) = (20, 30);
+const BISHOP_PAIR: (i32, i32) = (30, 50);
+const KNIGHT_OUTPOST: (i32, i32) = (25, 15);
+// Leichtfigur ohne Rückzug im gegnerischen Lager, z.B. Lxa7 b6
+const TRAPPED_MINOR: (i32, i32) = (-50, -50);
+// Turm in der Ecke, der vom unrochierten König eingesperrt wird
+const TRAPPED_ROOK: (i32, i32) = (-40, -10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MobilityWeights {
+    pub knight: (i32, i32, i32),
+    pub bishop: (i32, i32, i32),
+    pub rook: (i32, i32, i32),
+    pub queen: (i32, i32, i32),
+    pub rook_open_file: (i32, i32),
+    pub rook_semi_open_file: (i32, i32),
+    pub rook_on_seventh: (i32, i32),
+    pub bishop_pair: (i32, i32),
+    pub knight_outpost: (i32, i32),
+    pub trapped_minor: (i32, i32),
+    pub trapped_rook: (i32, i32),
+}
+
+impl Default for MobilityWeights {
+    fn default() -> Self {
+        MobilityWeights {
+            knight: KNIGHT_MOBILITY,
+            bishop: BISHOP_MOBILITY,
+            rook: ROOK_MOBILITY,
+            queen: QUEEN_MOBILITY,
+            rook_open_file: ROOK_OPEN_FILE,
+            rook_semi_open_file: ROOK_SEMI_OPEN_FILE,
+            rook_on_seventh: ROOK_ON_SEVENTH,
+            bishop_pair: BISHOP_PAIR,
+            knight_outpost: KNIGHT_OUTPOST,
+            trapped_minor: TRAPPED_MINOR,
+            trapped_rook: TRAPPED_ROOK,
+        }
+    }
+}
+
+// Beweglichkeit und Aktivität der Figuren als (Mittelspiel, Endspiel) aus Sicht von Weiß
+pub fn evaluate(brett: &Schach, weights: &MobilityWeights) -> (i32, i32) {
+    let (white_mg, white_eg) = evaluate_side(brett, &Color::White, weights);
+    let (black_mg, black_eg) = evaluate_side(brett, &Color::Black, weights);
+    (white_mg - black_mg, white_eg - black_eg)
+}
+
+fn evaluate_side(brett: &Schach, color: &Color, weights: &MobilityWeights) -> (i32, i32) {
+    let enemy = color.opposite();
+    let (king, queen, rooks, bishops, knights, own_pawns) = brett.bitboards(color);
+    let (enemy_king, _, _, _, _, enemy_pawns) = brett.bitboards(&enemy);
+    let occupied = brett.occupied();
+    // Felder ohne eigene Figur, die kein gegnerischer Bauer angreift
+    let safe = !brett.pieces_of(color) & !pawn_attacks(enemy_pawns, &enemy);
+    let (mut mg, mut eg) = (0, 0);
+    let mut add = |(a, b): (i32, i32)| {
+        mg += a;
+        eg += b;
+    };
+
+    let mobility = |attacks: u64, (base, per_mg, per_eg): (i32, i32, i32)| {
+        let count = (attacks & safe).count_ones() as i32;
+        ((count - base) * per_mg, (count - base) * per_eg)
+    };
+
+    for square in squares(knights) {
+        let attacks = LOOKUP_TABLE.get_knight_moves(square);
+        add(mobility(attacks, weights.knight));
+        // Vorposten: von einem Bauern gedeckt und von keinem gegnerischen Bauern mehr zu vertreiben
+        let rank = relative_rank(square, color);
+        let supported = pawn_attacks(own_pawns, color) >> square & 1 == 1;
+        let chasers = enemy_pawns & adjacent_files(square % 8) & ranks_ahead(square / 8, color);
+        if (3..=5).contains(&rank) && supported && chasers == 0 {
+            add(weights.knight_outpost);
+        }
+        if rank >= 4 && (attacks & safe).count_ones() <= 1 {
+            add(weights.trapped_minor);
+        }
+    }
+
+    for square in squares(bishops) {
+        let attacks = LOOKUP_TABLE.get_bishop_moves(square, occupied);
+        add(mobility(attacks, weights.bishop));
+        if relative_rank(square, color) >= 4 && (attacks & safe).count_ones() <= 1 {
+            add(weights.trapped_minor);
+        }
+    }
+    if bishops.count_ones() >= 2 {
+        add(weights.bishop_pair);
+    }
+
+    let (back_rank, seventh, eighth) = match color {
+        Color::White => (0xff << 56, 0xff << 8, 0xff),
+        Color::Black => (0xff, 0xff << 48, 0xff << 56),
+    };
+    for square in squares(rooks) {
+        let attacks = LOOKUP_TABLE.get_rook_moves(square, occupied);
+        add(mobility(attacks, weights.rook));
+        let file = FILE_A << (square % 8);
+        if (own_pawns | enemy_pawns) & file == 0 {
+            add(weights.rook_open_file);
+        } else if own_pawns & file == 0 {
+            add(weights.rook_semi_open_file);
+        }
+        // Auf der siebten Reihe, wenn dort Bauern zu holen sind oder der König eingesperrt wird
+        if seventh >> square & 1 == 1 && (enemy_pawns & seventh != 0 || enemy_king & eighth != 0) {
+            add(weights.rook_on_seventh);
+        }
+        // Der König steht auf der Grundreihe zwischen Turm und Brettmitte
+        let (rook_x, king_x) = (square % 8, king.trailing_zeros() as u64 % 8);
+        let boxed_in = match king_x {
+            1..=3 => rook_x < king_x,
+            5..=6 => rook_x > king_x,
+            _ => false,
+        };
+        if back_rank >> square & 1 == 1 && king & back_rank != 0 && boxed_in && (attacks & safe).count_ones() <= 3 {
+            add(weights.trapped_rook);
+        }
+    }
+
+    for square in squares(queen) {
+        add(mobility(LOOKUP_TABLE.get_queen_moves(square, occupied), weights.queen));
+    }
+    (mg, eg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white_activity(fen: &str) -> (i32, i32) {
+        evaluate_side(&Schach::from_fen(fen).unwrap(), &Color::White, &MobilityWeights::default())
+    }
+
+    #[test]
+    fn symmetric_start() {
+        assert_eq!(evaluate(&Schach::new(), &MobilityWeights::default()), (0, 0));
+    }
+
+    #[test]
+    fn rooks_and_bishops() {
+        // Gleiche Stellung, einmal mit offener e-Linie für den Turm
+        let closed = white_activity("6k1/pp2pppp/8/8/8/8/PP2PPPP/4R1K1 w - - 0 1");
+        let open = white_activity("6k1/pp3ppp/8/8/8/8/PP3PPP/4R1K1 w - - 0 1");
+        assert!(open.0 >= closed.0 + ROOK_OPEN_FILE.0, "{:?} {:?}", open, closed);
+
+        let pair = white_activity("6k1/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let single = white_activity("6k1/8/8/8/8/8/8/2B1KN2 w - - 0 1");
+        assert!(pair.1 - single.1 >= BISHOP_PAIR.1 - 10, "{:?} {:?}", pair, single);
+    }
+
+    #[test]
+    fn knight_outpost() {
+        let outpost = white_activity("6k1/pp4pp/8/4N3/3P4/8/8/6K1 w - - 0 1");
+        // Mit einem Bauern auf f7 kann der Springer vertrieben werden
+        let chased = white_activity("6k1/pp3ppp/8/4N3/3P4/8/8/6K1 w - - 0 1");
+        assert_eq!(outpost.0 - chased.0, KNIGHT_OUTPOST.0);
+    }
+
+    #[test]
+    fn trapped_pieces() {
+        // Lxa7 b6: der Läufer kommt nicht mehr heraus
+        let trapped = white_activity("6k1/B1p5/1p6/8/8/8/8/6K1 w - - 0 1");
+        assert!(trapped.0 <= TRAPPED_MINOR.0, "{:?}", trapped);
+        // Turm auf h1 hinter dem König auf f1
+        let rook = white_activity("6k1/8/8/8/8/8/6PP/5K1R w - - 0 1");
+        let mobility = (1 - ROOK_MOBILITY.0) * ROOK_MOBILITY.1;
+        assert_eq!(rook.0, mobility + TRAPPED_ROOK.0);
+    }
+}
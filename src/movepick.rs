@@ -0,0 +1,265 @@
+use crate::moves::Move;
+use crate::schach::{piece_value, Piece, Schach};
+use crate::search::MAX_DEPTH;
+
+// Killerzüge pro Ply und History pro Start- und Zielfeld, jeder Suchthread hat seine eigenen
+pub struct Heuristics {
+    killers: Vec<[Option<Move>; 2]>,
+    history: Vec<[i32; 64]>,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self {
+            killers: vec![[None; 2]; MAX_DEPTH as usize + 1],
+            history: vec![[0; 64]; 64],
+        }
+    }
+}
+
+impl Heuristics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers.get(ply).copied().unwrap_or([None; 2])
+    }
+
+    pub fn history(&self, m: Move) -> i32 {
+        self.history[m.from() as usize][m.to() as usize]
+    }
+
+    // Ein ruhiger Zug hat einen Beta-Cutoff ausgelöst
+    pub fn update(&mut self, m: Move, ply: usize, depth: u32) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(m) {
+                killers[1] = killers[0];
+                killers[0] = Some(m);
+            }
+        }
+        let entry = &mut self.history[m.from() as usize][m.to() as usize];
+        *entry += (depth * depth) as i32;
+        // Alle Werte halbieren, bevor sie zu groß werden
+        if *entry > 1 << 20 {
+            for row in self.history.iter_mut() {
+                for value in row.iter_mut() {
+                    *value /= 2;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GoodCaptures,
+    Killers,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Liefert die Züge einer Stellung nacheinander in der Reihenfolge, in der sie am ehesten einen Cutoff bringen:
+// Zug aus der Tabelle, gute Schlagzüge nach MVV-LVA, Killerzüge, ruhige Züge nach History, schlechte Schlagzüge.
+// Die Züge werden pseudolegal erzeugt und erst bei der Ausgabe auf Legalität geprüft
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    captures: Vec<(i32, Move)>,
+    quiets: Vec<(i32, Move)>,
+    bad_captures: Vec<Move>,
+    quiets_scored: bool,
+    captures_only: bool,
+}
+
+impl MovePicker {
+    pub fn new(brett: &Schach, hash_move: Option<Move>, killers: [Option<Move>; 2]) -> Self {
+        let moves = brett.generate_moves(u64::MAX);
+        // Der Zug aus der Tabelle kann von einer anderen Stellung mit gleichem Index stammen
+        let hash_move = hash_move.filter(|m| moves.contains(m));
+        let mut captures = Vec::new();
+        let mut quiets = Vec::new();
+        for m in moves {
+            if Some(m) == hash_move {
+                continue;
+            }
+            if m.is_capture() || m.promotion().is_some() {
+                captures.push((mvv_lva(brett, m), m));
+            } else {
+                quiets.push((0, m));
+            }
+        }
+        Self {
+            stage: Stage::HashMove,
+            hash_move,
+            killers: killers.map(|k| k.filter(|k| Some(*k) != hash_move)),
+            captures,
+            quiets,
+            bad_captures: Vec::new(),
+            quiets_scored: false,
+            captures_only: false,
+        }
+    }
+
+    // Für die Ruhesuche: nur Schlagzüge und Umwandlungen, Schlagzüge mit negativem SEE entfallen
+    pub fn captures(brett: &Schach) -> Self {
+        let mut picker = Self::new(brett, None, [None; 2]);
+        picker.quiets.clear();
+        picker.captures_only = true;
+        picker
+    }
+
+    pub fn next(&mut self, brett: &Schach, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GoodCaptures;
+                    if let Some(m) = self.hash_move.filter(|m| brett.is_legal(*m)) {
+                        return Some(m);
+                    }
+                },
+                Stage::GoodCaptures => {
+                    let Some((_, m)) = pick_best(&mut self.captures, |(score, _)| *score) else {
+                        self.stage = if self.captures_only { Stage::Done } else { Stage::Killers };
+                        continue;
+                    };
+                    // Verliert der Schlagzug Material, kommt er erst ganz am Ende dran
+                    if m.promotion().is_none() && brett.see(m) < 0 {
+                        if !self.captures_only {
+                            self.bad_captures.push(m);
+                        }
+                        continue;
+                    }
+                    if brett.is_legal(m) {
+                        return Some(m);
+                    }
+                },
+                Stage::Killers => {
+                    let Some(i) = self.killers.iter().position(|k| k.is_some()) else {
+                        self.stage = Stage::Quiets;
+                        continue;
+                    };
+                    let killer = self.killers[i].take().unwrap();
+                    // Killer stammen aus einer anderen Stellung, sie müssen hier erst einmal möglich sein
+                    if let Some(j) = self.quiets.iter().position(|(_, m)| *m == killer) {
+                        self.quiets.swap_remove(j);
+                        if brett.is_legal(killer) {
+                            return Some(killer);
+                        }
+                    }
+                },
+                Stage::Quiets => {
+                    if !self.quiets_scored {
+                        for (score, m) in self.quiets.iter_mut() {
+                            *score = heuristics.history(*m);
+                        }
+                        self.quiets_scored = true;
+                    }
+                    let Some((_, m)) = pick_best(&mut self.quiets, |(score, _)| *score) else {
+                        self.stage = Stage::BadCaptures;
+                        continue;
+                    };
+                    if brett.is_legal(m) {
+                        return Some(m);
+                    }
+                },
+                Stage::BadCaptures => {
+                    if self.bad_captures.is_empty() {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+                    let m = self.bad_captures.remove(0);
+                    if brett.is_legal(m) {
+                        return Some(m);
+                    }
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+// Wertvollstes Opfer zuerst, bei gleichem Opfer mit dem billigsten Angreifer
+fn mvv_lva(brett: &Schach, m: Move) -> i32 {
+    let victim = match m.is_en_passant() {
+        true => piece_value(&Piece::Pawn),
+        false => brett.piece_on(m.to()).map_or(0, |(p, _)| piece_value(&p)),
+    };
+    let attacker = brett.piece_on(m.from()).map_or(0, |(p, _)| piece_value(&p));
+    let promotion = m.promotion().map_or(0, |p| piece_value(&p));
+    10 * (victim + promotion) - attacker
+}
+
+// Selection Sort Schritt für Schritt, nach einem Cutoff muss der Rest nicht mehr sortiert werden.
+// Bei Gleichstand gewinnt der zuerst erzeugte Zug
+fn pick_best<T: Copy>(moves: &mut Vec<T>, score: impl Fn(&T) -> i32) -> Option<T> {
+    let mut best = 0;
+    for i in 1..moves.len() {
+        if score(&moves[i]) > score(&moves[best]) {
+            best = i;
+        }
+    }
+    if moves.is_empty() {
+        return None;
+    }
+    Some(moves.remove(best))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_moves(brett: &Schach, hash_move: Option<Move>, killers: [Option<Move>; 2], heuristics: &Heuristics) -> Vec<Move> {
+        let mut picker = MovePicker::new(brett, hash_move, killers);
+        std::iter::from_fn(|| picker.next(brett, heuristics)).collect()
+    }
+
+    #[test]
+    fn yields_every_legal_move_once() {
+        let heuristics = Heuristics::new();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        ] {
+            let brett = Schach::from_fen(fen).unwrap();
+            let legal = brett.get_all_legal_moves();
+            // Killer und Hashzug aus anderen Stellungen dürfen nicht durchrutschen
+            let foreign = Some(Move::new(0, 63, None, 0));
+            let mut picked = all_moves(&brett, Some(legal[3]), [Some(legal[5]), foreign], &heuristics);
+            assert_eq!(picked[0], legal[3]);
+            let mut picked_foreign = all_moves(&brett, foreign, [None; 2], &heuristics);
+            let mut legal = legal.clone();
+            picked.sort_by_key(|m| m.bits());
+            picked_foreign.sort_by_key(|m| m.bits());
+            legal.sort_by_key(|m| m.bits());
+            assert_eq!(picked, legal, "{}", fen);
+            assert_eq!(picked_foreign, legal, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn orders_by_stage() {
+        // Dxd5 ist gedeckt und kommt nach den ruhigen Zügen, exd5 zuerst
+        let brett = Schach::from_fen("4k3/8/2p5/3p4/4P3/8/8/3QK3 w - - 0 1").unwrap();
+        let mut heuristics = Heuristics::new();
+        let quiet = Move::new(59, 51, None, 0);
+        heuristics.update(Move::new(60, 61, None, 0), 5, 4);
+        let moves = all_moves(&brett, None, [Some(quiet), None], &heuristics);
+        assert_eq!(moves[0].to_string(), "e4d5");
+        assert_eq!(moves[1], quiet);
+        assert_eq!(moves[2].to_string(), "e1f1");
+        assert_eq!(moves.last().unwrap().to_string(), "d1d5");
+    }
+
+    #[test]
+    fn static_exchange() {
+        let brett = Schach::from_fen("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1").unwrap();
+        assert_eq!(brett.see(Move::new(60, 28, None, Move::CAPTURE)), 100);
+        let brett = Schach::from_fen("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1").unwrap();
+        assert!(brett.see(Move::new(43, 28, None, Move::CAPTURE)) < 0);
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+
+use crate::schach::Piece;
+
+// Ein Zug in 32 Bit:
+// Bits 0-5 Startfeld, Bits 6-11 Zielfeld (jeweils x + 8 * y), Bits 12-14 Umwandlungsfigur, ab Bit 15 Flags
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move(u32);
+
+impl Move {
+    pub const CAPTURE: u32     = 1 << 15;
+    pub const CASTLE: u32      = 1 << 16;
+    pub const EN_PASSANT: u32  = 1 << 17;
+    pub const DOUBLE_PUSH: u32 = 1 << 18;
+
+    pub fn new(from: u64, to: u64, promotion: Option<Piece>, flags: u32) -> Self {
+        let promotion = match promotion {
+            None => 0,
+            Some(Piece::Queen) => 1,
+            Some(Piece::Rook) => 2,
+            Some(Piece::Bishop) => 3,
+            Some(Piece::Knight) => 4,
+            Some(p) => panic!("cannot promote to {:?}", p),
+        };
+        Move(from as u32 | (to as u32) << 6 | promotion << 12 | flags)
+    }
+
+    pub fn from(&self) -> u64 {
+        (self.0 & 0x3f) as u64
+    }
+
+    pub fn to(&self) -> u64 {
+        (self.0 >> 6 & 0x3f) as u64
+    }
+
+    pub fn from_xy(&self) -> (u64, u64) {
+        (self.from() % 8, self.from() / 8)
+    }
+
+    pub fn to_xy(&self) -> (u64, u64) {
+        (self.to() % 8, self.to() / 8)
+    }
+
+    pub fn promotion(&self) -> Option<Piece> {
+        match self.0 >> 12 & 0b111 {
+            1 => Some(Piece::Queen),
+            2 => Some(Piece::Rook),
+            3 => Some(Piece::Bishop),
+            4 => Some(Piece::Knight),
+            _ => None,
+        }
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.0 & Move::CAPTURE != 0
+    }
+
+    pub fn is_castle(&self) -> bool {
+        self.0 & Move::CASTLE != 0
+    }
+
+    pub fn is_en_passant(&self) -> bool {
+        self.0 & Move::EN_PASSANT != 0
+    }
+
+    pub fn is_double_push(&self) -> bool {
+        self.0 & Move::DOUBLE_PUSH != 0
+    }
+
+    // Rohdarstellung für die Transpositionstabelle, passt in 19 Bit
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        Move(bits)
+    }
+}
+
+// Koordinatennotation wie in UCI, z.B. e2e4 oder a7a8q
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (x, y) in [self.from_xy(), self.to_xy()] {
+            write!(f, "{}{}", (b'a' + x as u8) as char, 8 - y)?;
+        }
+        match self.promotion() {
+            Some(Piece::Queen) => write!(f, "q"),
+            Some(Piece::Rook) => write!(f, "r"),
+            Some(Piece::Bishop) => write!(f, "b"),
+            Some(Piece::Knight) => write!(f, "n"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_and_decode() {
+        // e7 = 4 + 8 * 1, d8 = 3
+        let m = Move::new(12, 3, Some(Piece::Knight), Move::CAPTURE);
+        assert_eq!((m.from(), m.to()), (12, 3));
+        assert_eq!((m.from_xy(), m.to_xy()), ((4, 1), (3, 0)));
+        assert_eq!(m.promotion(), Some(Piece::Knight));
+        assert!(m.is_capture());
+        assert!(!m.is_castle() && !m.is_en_passant() && !m.is_double_push());
+        assert_eq!(Move::from_bits(m.bits()), m);
+        assert!(m.bits() < 1 << 19);
+
+        let castle = Move::new(60, 62, None, Move::CASTLE);
+        assert!(castle.is_castle() && !castle.is_capture());
+        assert_eq!(castle.promotion(), None);
+        let en_passant = Move::new(27, 18, None, Move::CAPTURE | Move::EN_PASSANT);
+        assert!(en_passant.is_en_passant() && en_passant.is_capture());
+        assert!(Move::new(52, 36, None, Move::DOUBLE_PUSH).is_double_push());
+        for square in [0, 7, 56, 63] {
+            let m = Move::new(square, 63 - square, None, 0);
+            assert_eq!((m.from(), m.to()), (square, 63 - square));
+        }
+    }
+
+    #[test]
+    fn coordinate_notation() {
+        assert_eq!(Move::new(52, 36, None, Move::DOUBLE_PUSH).to_string(), "e2e4");
+        assert_eq!(Move::new(60, 62, None, Move::CASTLE).to_string(), "e1g1");
+        assert_eq!(Move::new(56, 7, None, 0).to_string(), "a1h8");
+        for (piece, suffix) in [(Piece::Queen, "q"), (Piece::Rook, "r"), (Piece::Bishop, "b"), (Piece::Knight, "n")] {
+            assert_eq!(Move::new(8, 0, Some(piece), 0).to_string(), format!("a7a8{}", suffix));
+        }
+        assert_eq!(format!("{:?}", Move::new(55, 63, Some(Piece::Knight), Move::CAPTURE)), "h2h1n");
+    }
+}
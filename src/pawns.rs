@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+
+use crate::schach::{adjacent_files, ranks_ahead, relative_rank, squares, Color, FILE_A, FILE_H};
+
+// Bewertung der Bauernstruktur als (Mittelspiel, Endspiel) in Centipawns, jeweils pro Bauer
+const DOUBLED: (i32, i32) = (-10, -25);
+const ISOLATED: (i32, i32) = (-12, -15);
+const BACKWARD: (i32, i32) = (-8, -12);
+// Nach Reihe aus Sicht des Bauern, Index 1 ist die Grundreihe der Bauern
+const CONNECTED: [i32; 8] = [0, 4, 6, 9, 15, 25, 40, 0];
+const PASSED_MG: [i32; 8] = [0, 3, 6, 10, 20, 35, 55, 0];
+const PASSED_EG: [i32; 8] = [0, 6, 10, 20, 40, 70, 110, 0];
+
+// Gewichte der Bauernstruktur, ohne Angabe die Werte oben
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PawnWeights {
+    pub doubled: (i32, i32),
+    pub isolated: (i32, i32),
+    pub backward: (i32, i32),
+    pub connected: [i32; 8],
+    pub passed_mg: [i32; 8],
+    pub passed_eg: [i32; 8],
+}
+
+impl Default for PawnWeights {
+    fn default() -> Self {
+        PawnWeights {
+            doubled: DOUBLED,
+            isolated: ISOLATED,
+            backward: BACKWARD,
+            connected: CONNECTED,
+            passed_mg: PASSED_MG,
+            passed_eg: PASSED_EG,
+        }
+    }
+}
+
+// Einträge pro Thread, eine Zweierpotenz
+const TABLE_SIZE: usize = 1 << 14;
+
+// Ergebnis für eine Bauernstellung, aus Sicht von Weiß. Freibauern werden nur markiert, ihr Bonus hängt
+// auch von den übrigen Figuren ab und wird erst in passed_bonus berechnet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PawnEntry {
+    white_pawns: u64,
+    black_pawns: u64,
+    pub mg: i32,
+    pub eg: i32,
+    pub passed: u64,
+}
+
+// Die Bauernstruktur ändert sich selten, deshalb wird ihre Bewertung zwischengespeichert.
+// Jeder Suchthread hat seine eigene Tabelle. Sie gilt für einen Satz Gewichte und wird geleert, wenn ein
+// anderer Satz verwendet wird
+thread_local! {
+    static PAWN_TABLE: RefCell<(PawnWeights, Vec<Option<PawnEntry>>)> = RefCell::new((PawnWeights::default(), vec![None; TABLE_SIZE]));
+}
+
+fn slot(white_pawns: u64, black_pawns: u64) -> usize {
+    let index = (white_pawns.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ black_pawns.wrapping_mul(0xc2b2_ae3d_27d4_eb4f)) >> 50;
+    index as usize & (TABLE_SIZE - 1)
+}
+
+pub fn probe(white_pawns: u64, black_pawns: u64, weights: &PawnWeights) -> PawnEntry {
+    PAWN_TABLE.with(|table| {
+        let (table_weights, table) = &mut *table.borrow_mut();
+        if table_weights != weights {
+            *table_weights = weights.clone();
+            table.fill(None);
+        }
+        let slot = &mut table[slot(white_pawns, black_pawns)];
+        match slot {
+            Some(entry) if entry.white_pawns == white_pawns && entry.black_pawns == black_pawns => *entry,
+            _ => *slot.insert(evaluate(white_pawns, black_pawns, weights)),
+        }
+    })
+}
+
+pub fn evaluate(white_pawns: u64, black_pawns: u64, weights: &PawnWeights) -> PawnEntry {
+    let (white_mg, white_eg, white_passed) = evaluate_side(white_pawns, black_pawns, &Color::White, weights);
+    let (black_mg, black_eg, black_passed) = evaluate_side(black_pawns, white_pawns, &Color::Black, weights);
+    PawnEntry {
+        white_pawns,
+        black_pawns,
+        mg: white_mg - black_mg,
+        eg: white_eg - black_eg,
+        passed: white_passed | black_passed,
+    }
+}
+
+// Freibauern nach Reihe, mit halbem Bonus, wenn eine Figur auf dem Weg zur Umwandlung steht
+pub fn passed_bonus(entry: &PawnEntry, occupied: u64, weights: &PawnWeights) -> (i32, i32) {
+    let (mut mg, mut eg) = (0, 0);
+    for (pawns, color, sign) in [(entry.white_pawns, Color::White, 1), (entry.black_pawns, Color::Black, -1)] {
+        for square in squares(entry.passed & pawns) {
+            let (x, y) = (square % 8, square / 8);
+            let rank = relative_rank(square, &color);
+            let divisor = match occupied & FILE_A << x & ranks_ahead(y, &color) {
+                0 => 1,
+                _ => 2,
+            };
+            mg += sign * weights.passed_mg[rank] / divisor;
+            eg += sign * weights.passed_eg[rank] / divisor;
+        }
+    }
+    (mg, eg)
+}
+
+fn evaluate_side(own: u64, enemy: u64, color: &Color, weights: &PawnWeights) -> (i32, i32, u64) {
+    let enemy_attacks = pawn_attacks(enemy, &color.opposite());
+    let (mut mg, mut eg) = (0, 0);
+    let mut passed = 0;
+    let add = |(a, b): (i32, i32), mg: &mut i32, eg: &mut i32| {
+        *mg += a;
+        *eg += b;
+    };
+    for square in squares(own) {
+        let (x, y) = (square % 8, square / 8);
+        let rank = relative_rank(square, color);
+        let ahead = ranks_ahead(y, color);
+        let file = FILE_A << x;
+        let neighbours = adjacent_files(x);
+
+        // Der hintere von zwei Bauern auf einer Linie
+        let doubled = own & file & ahead != 0;
+        if doubled {
+            add(weights.doubled, &mut mg, &mut eg);
+        }
+        if enemy & (file | neighbours) & ahead == 0 && !doubled {
+            passed |= 1 << square;
+        }
+
+        // Gedeckt von einem Bauern schräg dahinter oder mit einem Nachbarn auf derselben Reihe
+        let supporters = pawn_attacks(1 << square, &color.opposite()) & own;
+        let phalanx = own & neighbours & 0xff << (8 * y);
+        if supporters | phalanx != 0 {
+            add((weights.connected[rank], weights.connected[rank]), &mut mg, &mut eg);
+        } else if own & neighbours == 0 {
+            add(weights.isolated, &mut mg, &mut eg);
+        } else if own & neighbours & !ahead == 0 && enemy_attacks & stop_square(square, color) != 0 {
+            // Alle Nachbarn sind schon vorbei, und das Feld davor wird von einem gegnerischen Bauern kontrolliert
+            add(weights.backward, &mut mg, &mut eg);
+        }
+    }
+    (mg, eg, passed)
+}
+
+// Alle Felder, die die Bauern einer Farbe angreifen
+pub fn pawn_attacks(pawns: u64, color: &Color) -> u64 {
+    match color {
+        Color::White => (pawns & !FILE_A) >> 9 | (pawns & !FILE_H) >> 7,
+        Color::Black => (pawns & !FILE_A) << 7 | (pawns & !FILE_H) << 9,
+    }
+}
+
+fn stop_square(square: u64, color: &Color) -> u64 {
+    match color {
+        Color::White => 1 << square >> 8,
+        Color::Black => 1 << square << 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bitboard aus Feldnamen wie "e4"
+    fn board(squares: &[&str]) -> u64 {
+        squares.iter().fold(0, |bitboard, square| {
+            let bytes = square.as_bytes();
+            let x = (bytes[0] - b'a') as u64;
+            let y = (b'8' - bytes[1]) as u64;
+            bitboard | 1 << (x + 8 * y)
+        })
+    }
+
+    #[test]
+    fn structure_terms() {
+        // Weiß: Doppelbauer auf c, isolierter Bauer auf a. Schwarz: verbundene Bauern f7/g6
+        let white = board(&["a2", "c2", "c3"]);
+        let black = board(&["f7", "g6"]);
+        let (mg, eg, _) = evaluate_side(white, black, &Color::White, &PawnWeights::default());
+        assert_eq!((mg, eg), (DOUBLED.0 + 3 * ISOLATED.0, DOUBLED.1 + 3 * ISOLATED.1));
+        let (mg, _, _) = evaluate_side(black, white, &Color::Black, &PawnWeights::default());
+        assert_eq!(mg, CONNECTED[2]);
+    }
+
+    #[test]
+    fn backward_pawn() {
+        // d3 deckt c4, kann selbst aber nicht mehr gedeckt werden, und d4 wird von e5 kontrolliert
+        let white = board(&["c4", "d3"]);
+        let black = board(&["e5"]);
+        let (mg, _, _) = evaluate_side(white, black, &Color::White, &PawnWeights::default());
+        assert_eq!(mg, CONNECTED[3] + BACKWARD.0);
+    }
+
+    #[test]
+    fn passed_pawns() {
+        let white = board(&["a5", "e4", "e3"]);
+        let black = board(&["d6", "h7"]);
+        let entry = evaluate(white, black, &PawnWeights::default());
+        // e3 steht hinter e4, h7 hat keinen weißen Bauern vor sich
+        assert_eq!(entry.passed, board(&["a5", "h7"]));
+        let free = passed_bonus(&entry, white | black, &PawnWeights::default());
+        assert_eq!(free, (PASSED_MG[4] - PASSED_MG[1], PASSED_EG[4] - PASSED_EG[1]));
+        // Eine Figur auf a7 halbiert den Bonus
+        let blocked = passed_bonus(&entry, white | black | board(&["a7"]), &PawnWeights::default());
+        assert_eq!(blocked.1, PASSED_EG[4] / 2 - PASSED_EG[1]);
+    }
+
+    #[test]
+    fn symmetric_and_cached() {
+        let weights = PawnWeights::default();
+        let white = board(&["a2", "b2", "c3", "d4", "f2", "g2", "h2"]);
+        let black = board(&["a7", "b7", "c6", "d5", "f7", "g7", "h7"]);
+        let entry = evaluate(white, black, &weights);
+        assert_eq!((entry.mg, entry.eg), (0, 0));
+        assert_eq!(probe(white, black, &weights), entry);
+        // Ein veränderter Eintrag in der Tabelle kommt unverändert zurück, es wird also nicht neu bewertet
+        let planted = PawnEntry { mg: 123, ..entry };
+        PAWN_TABLE.with(|table| table.borrow_mut().1[slot(white, black)] = Some(planted));
+        assert_eq!(probe(white, black, &weights), planted);
+        assert_ne!(probe(white, black & !board(&["d5"]), &weights), entry);
+    }
+
+    #[test]
+    fn other_weights_are_not_served_from_the_cache() {
+        let white = board(&["a2", "c2", "c3"]);
+        let black = board(&["f7", "g6"]);
+        let default = probe(white, black, &PawnWeights::default());
+        let harsh = PawnWeights { doubled: (-50, -50), ..Default::default() };
+        assert_eq!(probe(white, black, &harsh).mg, default.mg - 50 - DOUBLED.0);
+        assert_eq!(probe(white, black, &PawnWeights::default()), default);
+    }
+}
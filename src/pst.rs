@@ -0,0 +1,253 @@
+use crate::evaluation::Weights;
+use crate::schach::{Color, Piece};
+
+// Figurenwerte je Feld für Mittel- und Endspiel, zusätzlich zum Material (Werte nach PeSTO).
+// Die Tabellen sind aus Sicht von Weiß, Index 0 ist a8 wie bei den Bitboards, für Schwarz wird gespiegelt
+
+// Spielphase mit allen Leichtfiguren, Türmen und Damen auf dem Brett, bei 0 gilt nur noch die Endspieltabelle
+pub const PHASE_MAX: i32 = 24;
+
+pub fn phase_weight(p: &Piece) -> i32 {
+    match p {
+        Piece::Queen => 4,
+        Piece::Rook => 2,
+        Piece::Bishop | Piece::Knight => 1,
+        Piece::King | Piece::Pawn => 0,
+    }
+}
+
+// Feldwerte je Figur in der Reihenfolge von Piece, also der König zuerst
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tables {
+    pub mg: [[i32; 64]; 6],
+    pub eg: [[i32; 64]; 6],
+}
+
+impl Default for Tables {
+    fn default() -> Self {
+        Tables {
+            mg: [MG_KING, MG_QUEEN, MG_ROOK, MG_BISHOP, MG_KNIGHT, MG_PAWN],
+            eg: [EG_KING, EG_QUEEN, EG_ROOK, EG_BISHOP, EG_KNIGHT, EG_PAWN],
+        }
+    }
+}
+
+// Material und Feldwert je Figur, Farbe und Feld als (Mittelspiel, Endspiel), positiv für beide Farben.
+// Das Brett führt damit seine Summen mit, die Tabellen für Schwarz sind schon gespiegelt
+#[derive(Debug, PartialEq, Eq)]
+pub struct PieceValues {
+    values: [[(i32, i32); 64]; 12],
+}
+
+impl PieceValues {
+    // material in der Reihenfolge von Piece
+    pub fn new(material: &[i32; 6], tables: &Tables) -> Self {
+        let mut values = [[(0, 0); 64]; 12];
+        for piece in 0..6 {
+            for square in 0..64 {
+                let (mg, eg) = (tables.mg[piece][square], tables.eg[piece][square]);
+                values[piece][square] = (material[piece] + mg, material[piece] + eg);
+                values[piece + 6][square ^ 56] = (material[piece] + mg, material[piece] + eg);
+            }
+        }
+        PieceValues { values }
+    }
+
+    pub fn get(&self, p: &Piece, c: &Color, square: u64) -> (i32, i32) {
+        self.values[p.index() + 6 * c.index()][square as usize]
+    }
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        let weights = Weights::default();
+        PieceValues::new(&weights.material, &weights.pst)
+    }
+}
+
+// Überblendung zwischen Mittel- und Endspielwert nach der Phase
+pub fn taper(mg: i32, eg: i32, phase: i32) -> i32 {
+    let phase = phase.clamp(0, PHASE_MAX);
+    (mg * phase + eg * (PHASE_MAX - phase)) / PHASE_MAX
+}
+
+#[rustfmt::skip]
+const MG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     98, 134,  61,  95,  68, 126,  34, -11,
+     -6,   7,  26,  31,  65,  56,  25, -20,
+    -14,  13,   6,  21,  23,  12,  17, -23,
+    -27,  -2,  -5,  12,  17,   6,  10, -25,
+    -26,  -4,  -4, -10,   3,   3,  33, -12,
+    -35,  -1, -20, -23, -15,  24,  38, -22,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const EG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const MG_KNIGHT: [i32; 64] = [
+   -167, -89, -34, -49,  61, -97, -15,-107,
+    -73, -41,  72,  36,  23,  62,   7, -17,
+    -47,  60,  37,  65,  84, 129,  73,  44,
+     -9,  17,  19,  53,  37,  69,  18,  22,
+    -13,   4,  16,  13,  28,  19,  21,  -8,
+    -23,  -9,  12,  10,  19,  17,  25, -16,
+    -29, -53, -12,  -3,  -1,  18, -14, -19,
+   -105, -21, -58, -33, -17, -28, -19, -23,
+];
+
+#[rustfmt::skip]
+const EG_KNIGHT: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+#[rustfmt::skip]
+const MG_BISHOP: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+#[rustfmt::skip]
+const EG_BISHOP: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+];
+
+#[rustfmt::skip]
+const MG_ROOK: [i32; 64] = [
+     32,  42,  32,  51,  63,   9,  31,  43,
+     27,  32,  58,  62,  80,  67,  26,  44,
+     -5,  19,  26,  36,  17,  45,  61,  16,
+    -24, -11,   7,  26,  24,  35,  -8, -20,
+    -36, -26, -12,  -1,   9,  -7,   6, -23,
+    -45, -25, -16, -17,   3,   0,  -5, -33,
+    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+    -19, -13,   1,  17,  16,   7, -37, -26,
+];
+
+#[rustfmt::skip]
+const EG_ROOK: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4,  -20,
+];
+
+#[rustfmt::skip]
+const MG_QUEEN: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+#[rustfmt::skip]
+const EG_QUEEN: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+#[rustfmt::skip]
+const MG_KING: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+#[rustfmt::skip]
+const EG_KING: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schach::{assert_consistent, Schach};
+
+    #[test]
+    fn incremental_matches_fresh() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        ] {
+            assert_consistent(&mut Schach::from_fen(fen).unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn mirrored_positions_are_equal() {
+        let white = Schach::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let black = Schach::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3").unwrap();
+        assert_eq!(white.eval_position(), -black.eval_position());
+        assert_eq!(Schach::new().eval_position().0, 0);
+        assert_eq!(Schach::new().phase(), PHASE_MAX);
+    }
+
+    #[test]
+    fn phase_blends_the_tables() {
+        // Im Endspiel zählt der aktive König, im Mittelspiel die Sicherheit
+        assert_eq!(taper(100, -20, PHASE_MAX), 100);
+        assert_eq!(taper(100, -20, 0), -20);
+        assert_eq!(taper(100, -20, 12), 40);
+        let brett = Schach::from_fen("8/p7/8/3k4/8/8/P7/K7 w - - 0 1").unwrap();
+        assert_eq!(brett.phase(), 0);
+        assert!(brett.eval_position().0 < 0);
+    }
+}
@@ -1,17 +1,20 @@
-use std::collections::{HashMap, HashSet};
-use std::time::{Duration, SystemTime};
-use rayon::iter::IndexedParallelIterator;
-use rayon::prelude::*;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use std::fmt;
+use std::sync::Arc;
 
+use crate::evaluation::{Evaluator, HAND_CRAFTED};
 use crate::lookup_table;
+use crate::pst;
+use crate::zobrist;
+use crate::moves::Move;
+use crate::score::Score;
 use lazy_static::lazy_static;
 // use chess_notation_parser::{self, Turn, CastlingType, Castling};
 
 
 lazy_static! {
     pub static ref LOOKUP_TABLE: lookup_table::LookupTable = lookup_table::LookupTable::new();
+    pub static ref ZOBRIST: zobrist::ZobristKeys = zobrist::ZobristKeys::new();
+    pub static ref PIECE_VALUES: Arc<pst::PieceValues> = Arc::new(pst::PieceValues::default());
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -20,7 +23,24 @@ pub enum Color {
     Black
 }
 
-#[derive(Clone, PartialEq, Eq)]
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+
+    // Index für Tabellen je Farbe
+    pub fn index(&self) -> usize {
+        match self {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Piece {
     King,
     Queen,
@@ -30,14 +50,84 @@ pub enum Piece {
     Pawn
 }
 
-#[derive(PartialEq)]
+impl Piece {
+    // Index für Tabellen je Figur in der Reihenfolge der Varianten
+    pub fn index(&self) -> usize {
+        match self {
+            Piece::King => 0,
+            Piece::Queen => 1,
+            Piece::Rook => 2,
+            Piece::Bishop => 3,
+            Piece::Knight => 4,
+            Piece::Pawn => 5,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DrawReason {
+    Stalemate,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    Repetition,
+    Agreement,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Outcome {
     Checkmate(Color),
-    Stalemate,
+    Draw(DrawReason),
     None,
 }
 
-#[derive(Clone, PartialEq, Hash, Eq)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    InvalidPiece(char),
+    InvalidRank(usize),
+    InvalidActivePlayer(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidCounter(String),
+    MissingKing(Color),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "FEN is missing the {} field", field),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece '{}' in FEN", c),
+            FenError::InvalidRank(rank) => write!(f, "rank {} in FEN does not describe 8 squares", rank),
+            FenError::InvalidActivePlayer(s) => write!(f, "invalid active player '{}' in FEN", s),
+            FenError::InvalidCastling(s) => write!(f, "invalid castling rights '{}' in FEN", s),
+            FenError::InvalidEnPassant(s) => write!(f, "invalid en passant square '{}' in FEN", s),
+            FenError::InvalidCounter(s) => write!(f, "invalid move counter '{}' in FEN", s),
+            FenError::MissingKing(c) => write!(f, "FEN has no single {:?} king", c),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+#[derive(Debug, Clone)]
+pub struct NullMoveUndo {
+    en_passant: Option<(i32,i32)>,
+    fifty_move: u32,
+    hash: u64,
+}
+
+// Alles, was unmake_move braucht, um einen Zug zurückzunehmen
+#[derive(Debug, Clone)]
+pub struct Undo {
+    piece: Piece,
+    captured: Option<Piece>,
+    castle: u64,
+    en_passant: Option<(i32,i32)>,
+    fifty_move: u32,
+    hash: u64,
+}
+
+#[derive(Clone, PartialEq, Eq)]
 pub struct Schach {
     pub active_player: Color,
     black_pawns: u64,
@@ -55,11 +145,22 @@ pub struct Schach {
     castle: u64,
     en_passant: Option<(i32,i32)>,
     fifty_move: u32,
+    fullmove: u32,
+    // Zobrist-Schlüssel, wird bei jeder Änderung der Stellung mitgeführt
+    hash: u64,
+    // Schlüssel aller vorherigen Stellungen der Partie
+    history: Vec<u64>,
+    // Material und Feldtabellen aus Sicht von Weiß für Mittel- und Endspiel sowie die Spielphase,
+    // werden wie der Schlüssel bei jeder Änderung mitgeführt. Die Werte dafür kommen aus piece_values
+    eval_mg: i32,
+    eval_eg: i32,
+    phase: i32,
+    piece_values: Arc<pst::PieceValues>,
 }
 
 impl Schach {
     pub fn new() -> Self {
-        Schach {
+        let mut brett = Schach {
             active_player: Color::White,
             black_pawns   : 0b00000000_00000000_00000000_00000000_00000000_00000000_11111111_00000000,
             white_pawns   : 0b00000000_11111111_00000000_00000000_00000000_00000000_00000000_00000000,
@@ -76,223 +177,432 @@ impl Schach {
             castle        : 0b10010001_00000000_00000000_00000000_00000000_00000000_00000000_10010001,
             en_passant    : None,
             fifty_move    : 0, 
-        }
+            fullmove      : 1,
+            hash          : 0,
+            history       : Vec::new(),
+            eval_mg       : 0,
+            eval_eg       : 0,
+            phase         : 0,
+            piece_values  : Arc::clone(&PIECE_VALUES),
+        };
+        brett.hash = brett.compute_hash();
+        brett.compute_eval();
+        brett
     }
 
-    fn get_all_legal_moves(&self) -> Vec<(u64, u64, u64, u64)> {
-        let mut result: Vec<(u64, u64, u64, u64)> = Vec::new();
-        
-        for x in 0..8 {
-            for y in 0..8 {
-                let mut m: Vec<(u64, u64, u64, u64)> = self.get_legal_moves(x, y, 1).iter().map(| (a,b) | (x,y,*a as u64, *b as u64)).collect();
-                result.append(&mut m);
-            }
-        }
-        result.shuffle(&mut thread_rng());
-        result
-    }
+    pub fn from_fen(fen: &str) -> Result<Schach, FenError> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or(FenError::MissingField("piece placement"))?;
+        let active = fields.next().ok_or(FenError::MissingField("active player"))?;
+        let castling = fields.next().ok_or(FenError::MissingField("castling"))?;
+        let en_passant = fields.next().ok_or(FenError::MissingField("en passant"))?;
+        // Die Zugzähler fehlen in manchen FENs (z.B. EPD), dann gelten die Startwerte
+        let fifty_move = fields.next().unwrap_or("0");
+        let fullmove = fields.next().unwrap_or("1");
 
+        let mut brett = Schach {
+            active_player: Color::White,
+            black_pawns: 0,
+            white_pawns: 0,
+            black_king: 0,
+            white_king: 0,
+            black_queen: 0,
+            white_queen: 0,
+            black_bishops: 0,
+            white_bishops: 0,
+            black_knights: 0,
+            white_knights: 0,
+            black_rooks: 0,
+            white_rooks: 0,
+            castle: 0,
+            en_passant: None,
+            fifty_move: 0,
+            fullmove: 1,
+            hash: 0,
+            history: Vec::new(),
+            eval_mg: 0,
+            eval_eg: 0,
+            phase: 0,
+            piece_values: Arc::clone(&PIECE_VALUES),
+        };
 
-    pub fn minmax(self, depth:u64, mut alpha: f32, mut beta: f32, maximizing_player: bool, eval_map: &mut HashMap<Schach, f32> ) -> f32 {
-        if eval_map.contains_key(&self) {
-            return eval_map[&self];
-        }
-        if depth == 0 {
-            let eval = self.eval_position();   
-            eval_map.insert(self, eval);
-            return eval;
+        // FEN beginnt mit Reihe 8, das entspricht y = 0
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::InvalidRank(ranks.len()));
         }
-        match self.get_outcome() { 
-            Outcome::None => () , 
-            Outcome::Stalemate => {
-                let eval = self.eval_position();   
-                eval_map.insert(self, eval);
-                return eval;
-            }
-            Outcome::Checkmate(_) =>  {
-                let bonus = match maximizing_player {
-                    true  => -(depth as f32) * 100.0,
-                    false => depth as f32 * 100.0,
+        for (y, rank) in ranks.iter().enumerate() {
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    x += empty as u64;
+                    continue;
+                }
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let piece = match c.to_ascii_lowercase() {
+                    'k' => Piece::King,
+                    'q' => Piece::Queen,
+                    'r' => Piece::Rook,
+                    'b' => Piece::Bishop,
+                    'n' => Piece::Knight,
+                    'p' => Piece::Pawn,
+                    _ => return Err(FenError::InvalidPiece(c)),
                 };
-                let eval = self.eval_position() + bonus;   
-                eval_map.insert(self, eval);
-                return eval;
+                if x >= 8 {
+                    return Err(FenError::InvalidRank(8 - y));
+                }
+                brett.set_piece(&piece, &color, x, y as u64);
+                x += 1;
+            }
+            if x != 8 {
+                return Err(FenError::InvalidRank(8 - y));
             }
         }
+        if brett.white_king.count_ones() != 1 {
+            return Err(FenError::MissingKing(Color::White));
+        }
+        if brett.black_king.count_ones() != 1 {
+            return Err(FenError::MissingKing(Color::Black));
+        }
 
-        if maximizing_player {
-            let mut max_eval = f32::NEG_INFINITY;
-            for (a,b,c,d) in self.get_all_legal_moves() {
-                let mut brett = self.clone();
-                brett.move_piece(a, b, c, d);
-                let eval = brett.minmax(depth - 1 ,alpha, beta, false, eval_map);
-                eval_map.insert(self.clone(), eval);
-                max_eval = max_eval.max(eval);
-                alpha = alpha.max(eval);
-                if beta <= alpha {
-                    break;
+        brett.active_player = match active {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::InvalidActivePlayer(active.to_string())),
+        };
+
+        // castle enthält die Felder von König und Turm, die noch nicht gezogen haben
+        if castling != "-" {
+            for c in castling.chars() {
+                let (king, rook, rook_bitboard) = match c {
+                    'K' => (60, 63, brett.white_rooks),
+                    'Q' => (60, 56, brett.white_rooks),
+                    'k' => (4, 7, brett.black_rooks),
+                    'q' => (4, 0, brett.black_rooks),
+                    _ => return Err(FenError::InvalidCastling(castling.to_string())),
+                };
+                let king_bitboard = if king == 60 { brett.white_king } else { brett.black_king };
+                if king_bitboard >> king & 1 == 0 || rook_bitboard >> rook & 1 == 0 {
+                    return Err(FenError::InvalidCastling(castling.to_string()));
                 }
-            }   
-            return max_eval;
-        } else {
-            let mut min_eval = f32::INFINITY;
-            for (a,b,c,d) in self.get_all_legal_moves() {
-                let mut brett = self.clone();
-                brett.move_piece(a, b, c, d);
-                let eval = brett.minmax(depth - 1, alpha, beta, true, eval_map);
-                eval_map.insert(self.clone(), eval);
-                min_eval = min_eval.min(eval);
-                beta = beta.min(eval);
-                if beta <= alpha {
-                    break;
+                brett.castle |= 1 << king | 1 << rook;
+            }
+        }
+
+        if en_passant != "-" {
+            let square = Schach::parse_square(en_passant).ok_or(FenError::InvalidEnPassant(en_passant.to_string()))?;
+            let expected_rank = match brett.active_player { Color::White => 2, Color::Black => 5 };
+            if square.1 != expected_rank {
+                return Err(FenError::InvalidEnPassant(en_passant.to_string()));
+            }
+            brett.en_passant = Some(square);
+        }
+
+        brett.fifty_move = fifty_move.parse().map_err(|_| FenError::InvalidCounter(fifty_move.to_string()))?;
+        brett.fullmove = fullmove.parse().map_err(|_| FenError::InvalidCounter(fullmove.to_string()))?;
+        if brett.fullmove == 0 {
+            return Err(FenError::InvalidCounter(fullmove.to_string()));
+        }
+        brett.hash = brett.compute_hash();
+        brett.compute_eval();
+        Ok(brett)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for y in 0..8 {
+            let mut empty = 0;
+            for x in 0..8 {
+                match self.get_piece_at(x, y) {
+                    Some((p, c)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let symbol = match p {
+                            Piece::King => 'k',
+                            Piece::Queen => 'q',
+                            Piece::Rook => 'r',
+                            Piece::Bishop => 'b',
+                            Piece::Knight => 'n',
+                            Piece::Pawn => 'p',
+                        };
+                        fen.push(if c == Color::White { symbol.to_ascii_uppercase() } else { symbol });
+                    }
+                    None => empty += 1,
                 }
             }
-            return min_eval;
-        } 
-    }   
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y < 7 {
+                fen.push('/');
+            }
+        }
 
+        fen.push_str(match self.active_player { Color::White => " w ", Color::Black => " b " });
 
-    pub fn best_move(&self, depth: u64, start: SystemTime) -> (u64,u64,u64,u64) {
-        let mut best = f32::MIN;
-        
-        let maximizing_player = match self.active_player {
-            Color::Black => true,
-            Color::White => false,
-        };
-        let factor = match self.active_player {
-            Color::Black => -1.0,
-            Color::White =>  1.0,
-        };
-        
-        let all_moves = self.get_all_legal_moves();
-        let mut best_move = all_moves[0];
-        let mut moves: Vec<(f32,u64,u64,u64,u64)> = Vec::new();
-        all_moves.par_iter()
-        .map(|(a,b,c,d)| {
-            let mut brett = self.clone();
-            brett.move_piece(*a, *b, *c, *d);
-            let eval = match self.get_outcome() { 
-                Outcome::Stalemate    => factor * self.eval_position(),
-                Outcome::Checkmate(_) => factor * self.eval_position() + depth as f32,
-                Outcome::None => {
-                    let mut eval_map:HashMap<Schach, f32>  = HashMap::new();
-                    factor *  brett.minmax(depth, f32::NEG_INFINITY, f32::INFINITY, maximizing_player, &mut eval_map)
-                },
-            };
-            (eval ,*a,*b,*c,*d)
-        }).collect_into_vec(&mut moves);
-        
-        if SystemTime::now() < start + Duration::new(0,1_000_000_000/3) && depth < 25 {
-            return self.best_move(depth+1,start);
-        }
-        
-        for (eval,a,b,c,d) in moves {
-            if eval > best {
-                best = eval;
-                best_move = (a,b,c,d);
+        let mut castling = String::new();
+        for (king, rook, symbol) in [(60, 63, 'K'), (60, 56, 'Q'), (4, 7, 'k'), (4, 0, 'q')] {
+            if self.castle >> king & 1 == 1 && self.castle >> rook & 1 == 1 {
+                castling.push(symbol);
             }
         }
-        println!("tiefe: {}, eval: {:.2}, time: {:?}", depth+1, factor * best, SystemTime::now().duration_since(start).unwrap());
-        
-        best_move
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+
+        match self.en_passant {
+            Some((x, y)) => fen.push_str(&format!(" {}{}", (b'a' + x as u8) as char, 8 - y)),
+            None => fen.push_str(" -"),
+        }
+        fen.push_str(&format!(" {} {}", self.fifty_move, self.fullmove));
+        fen
+    }
+
+    fn parse_square(square: &str) -> Option<(i32, i32)> {
+        let mut chars = square.chars();
+        let file = chars.next()?;
+        let rank = chars.next()?.to_digit(10)? as i32;
+        if chars.next().is_some() || !('a'..='h').contains(&file) || !(1..=8).contains(&rank) {
+            return None;
+        }
+        Some((file as i32 - 'a' as i32, 8 - rank))
+    }
+
+    pub fn get_all_legal_moves(&self) -> Vec<Move> {
+        self.generate_moves(u64::MAX).into_iter().filter(|m| self.is_legal(*m)).collect()
+    }
+
+
+    // Schlagzüge und Umwandlungen für die Ruhesuche
+    pub fn get_legal_captures(&self) -> Vec<Move> {
+        self.generate_moves(u64::MAX).into_iter().filter(|m| (m.is_capture() || m.promotion().is_some()) && self.is_legal(*m)).collect()
+    }
+
+    // Aus Sicht von Weiß, mit der eingebauten Bewertung
+    pub fn eval_position(&self) -> Score {
+        self.eval_position_with(&*HAND_CRAFTED)
     }
 
-    pub fn eval_position(&self) -> f32 {
+    pub fn eval_position_with<E: Evaluator>(&self, evaluator: &E) -> Score {
         match self.get_outcome() {
-            Outcome::Checkmate(Color::White) => return 1000.0,
-            Outcome::Checkmate(Color::Black) => return -1000.0,
-            Outcome::Stalemate => return 0.0,
-            Outcome::None => (),
-        }
-        let mut eval:f32 = 0.0;
-        for (c,p,_, _) in self.get_positions() {
-            let value = match (&c,&p) {
-                (Color::White, Piece::King) => 0.0,
-                (Color::White, Piece::Queen) => 9.5,
-                (Color::White, Piece::Rook) => 5.63,
-                (Color::White, Piece::Bishop) => 3.33,
-                (Color::White, Piece::Knight) => 3.05,
-                (Color::White, Piece::Pawn) => 1.0,
-                (Color::Black, Piece::King) => 0.0,
-                (Color::Black, Piece::Queen) => -9.5,
-                (Color::Black, Piece::Rook) => -5.63,
-                (Color::Black, Piece::Bishop) => -3.33,
-                (Color::Black, Piece::Knight) => -3.05,
-                (Color::Black, Piece::Pawn) => -1.0,
-            };
-            eval += value;
-        }
-        eval 
-    }
-
-    pub fn move_piece(&mut self, from_x: u64, from_y: u64, to_x: u64, to_y: u64) {
-        if let Some((p_f,c_f)) = self.get_piece_at(from_x, from_y) {
-            self.en_passant = None;
-            self.fifty_move += 1;
-            // Update En-Passant und 50 move
-            if p_f == Piece::Pawn {
-                self.fifty_move = 0;
-                if (to_y as i32 - from_y as i32).abs() == 2 {
-                    self.en_passant = match c_f { 
-                        Color::White => Some((to_x as i32, to_y as i32 + 1)),
-                        Color::Black => Some((to_x as i32, to_y as i32 - 1)),
-                    };
-                }
+            Outcome::Checkmate(Color::White) => Score::MATE,
+            Outcome::Checkmate(Color::Black) => -Score::MATE,
+            Outcome::Draw(_) => Score::DRAW,
+            Outcome::None => evaluator.evaluate(self),
+        }
+    }
+
+    // Material und Feldwerte aus piece_values als (Mittelspiel, Endspiel) aus Sicht von Weiß
+    pub fn material_and_pst(&self) -> (i32, i32) {
+        (self.eval_mg, self.eval_eg)
+    }
+
+    pub fn piece_values(&self) -> &Arc<pst::PieceValues> {
+        &self.piece_values
+    }
+
+    // Andere Material- und Feldwerte, z.B. aus einer Datei mit Gewichten. Die Summen werden einmal neu berechnet
+    pub fn set_piece_values(&mut self, values: Arc<pst::PieceValues>) {
+        if !Arc::ptr_eq(&self.piece_values, &values) {
+            self.piece_values = values;
+            self.compute_eval();
+        }
+    }
+
+    // PHASE_MAX mit allen Figuren, 0 wenn nur noch Könige und Bauern übrig sind
+    pub fn phase(&self) -> i32 {
+        self.phase
+    }
+
+    // Ohne Angabe wird ein Bauer auf der letzten Reihe zur Dame
+    pub fn make_move(&mut self, m: Move) -> Undo {
+        let (from_x, from_y) = m.from_xy();
+        let (to_x, to_y) = m.to_xy();
+        // Der Zug muss aus der Zuggenerierung für diese Stellung stammen
+        let Some((p_f, c_f)) = self.get_piece_at(from_x, from_y) else {
+            panic!("no piece on the start square of {}, the move must come from the move generator", m);
+        };
+        let undo = Undo {
+            piece: p_f.clone(),
+            captured: self.get_piece_at(to_x, to_y).map(|(p, _)| p),
+            castle: self.castle,
+            en_passant: self.en_passant,
+            fifty_move: self.fifty_move,
+            hash: self.hash,
+        };
+
+        self.history.push(self.hash);
+        self.hash ^= ZOBRIST.en_passant(self.en_passant) ^ ZOBRIST.castle(self.castle);
+        self.en_passant = None;
+        self.fifty_move += 1;
+        // Update En-Passant und 50 move
+        if p_f == Piece::Pawn {
+            self.fifty_move = 0;
+            if (to_y as i32 - from_y as i32).abs() == 2 {
+                self.en_passant = match c_f { 
+                    Color::White => Some((to_x as i32, to_y as i32 + 1)),
+                    Color::Black => Some((to_x as i32, to_y as i32 - 1)),
+                };
             }
+        }
+
+        // Update castle, auch ein geschlagener Turm darf nicht mehr rochieren
+        self.castle &= !(1 << (from_x + 8 * from_y)) & !(1 << (to_x + 8 * to_y));
+        self.hash ^= ZOBRIST.en_passant(self.en_passant) ^ ZOBRIST.castle(self.castle) ^ ZOBRIST.black_to_move();
 
-            // Update castle
-            if (p_f == Piece::Rook || p_f == Piece::King) && self.castle >> (from_x + 8 * from_y) & 1 == 1  {
-                self.castle -= 1 << (from_x + 8 * from_y);
+        if let Some((p_t,c_t)) = self.get_piece_at(to_x, to_y) {
+            self.fifty_move = 0;
+            self.remove_piece_at(&p_t, &c_t, to_x, to_y);
+        } else if p_f == Piece::Pawn && to_x != from_x {
+            // En-passant zug ausführen
+            self.remove_piece_at(&Piece::Pawn, &self.opponent(), to_x, from_y);
+        } else if p_f == Piece::King && (to_x as i32 - from_x as i32).abs() == 2 {
+            // Castle Ausführen 
+            if to_x > from_x {
+                self.set_piece(&Piece::Rook, &c_f, to_x - 1, to_y);
+                self.remove_piece_at(&Piece::Rook, &c_f, from_x + 3, from_y);
+            } else {
+                self.set_piece(&Piece::Rook, &c_f, to_x + 1, to_y);
+                self.remove_piece_at(&Piece::Rook, &c_f, from_x - 4, from_y);
             }
+        }
 
-            if let Some((p_t,c_t)) = self.get_piece_at(to_x, to_y) {
-                self.fifty_move = 0;
-                if p_f == Piece::Pawn && (to_y == 7 || to_y == 0) { 
-                    self.remove_piece_at(&p_t, &c_t, to_x, to_x);
-                    self.set_piece(&Piece::Queen, &c_f, to_x, to_y);
-                    self.remove_piece_at(&p_f, &c_f, from_x, from_y);
-                } else {
-                    self.remove_piece_at(&p_t, &c_t, to_x, to_y);
-                    self.set_piece(&p_f, &c_f, to_x, to_y);
-                    self.remove_piece_at(&p_f, &c_f, from_x, from_y);
-                }
+        self.remove_piece_at(&p_f, &c_f, from_x, from_y);
+        if p_f == Piece::Pawn && (to_y == 7 || to_y == 0) { 
+            self.set_piece(&m.promotion().unwrap_or(Piece::Queen), &c_f, to_x, to_y);
+        } else {
+            self.set_piece(&p_f, &c_f, to_x, to_y);
+        }
+
+        // Update active player
+        self.active_player = match self.active_player {
+            Color::White => Color::Black,
+            Color::Black => {
+                self.fullmove += 1;
+                Color::White
+            },
+        };
+        undo
+    }
+
+    pub fn unmake_move(&mut self, m: Move, undo: Undo) {
+        let piece = undo.piece;
+        let (from_x, from_y) = m.from_xy();
+        let (to_x, to_y) = m.to_xy();
+
+        self.active_player = match self.active_player {
+            Color::White => {
+                self.fullmove -= 1;
+                Color::Black
+            },
+            Color::Black => Color::White,
+        };
+        let c_f = self.active_player.clone();
+
+        self.remove_at(to_x, to_y);
+        self.set_piece(&piece, &c_f, from_x, from_y);
+        if let Some(p_t) = &undo.captured {
+            self.set_piece(p_t, &self.opponent(), to_x, to_y);
+        } else if piece == Piece::Pawn && to_x != from_x {
+            self.set_piece(&Piece::Pawn, &self.opponent(), to_x, from_y);
+        } else if piece == Piece::King && (to_x as i32 - from_x as i32).abs() == 2 {
+            if to_x > from_x {
+                self.remove_piece_at(&Piece::Rook, &c_f, to_x - 1, to_y);
+                self.set_piece(&Piece::Rook, &c_f, from_x + 3, from_y);
             } else {
-                if p_f == Piece::Pawn && (to_y == 7 || to_y == 0) { 
-                    self.set_piece(&Piece::Queen, &c_f, to_x, to_y);
-                    self.remove_piece_at(&p_f, &c_f, from_x, from_y);
-                }
-                // Castle Ausführen 
-                 else if p_f == Piece::King && (to_x as i32 - from_x as i32).abs() == 2 {
-                    self.set_piece(&p_f, &c_f, to_x, to_y);
-                    self.remove_piece_at(&p_f, &c_f, from_x, from_y);
-                    if to_x as i32 - from_x as i32 == 2 {
-                        self.set_piece(&Piece::Rook, &c_f, to_x - 1, to_y);
-                        self.remove_piece_at(&Piece::Rook, &c_f, from_x + 3, from_y);
-                    } else {
-                        self.set_piece(&Piece::Rook, &c_f, to_x + 1, to_y);
-                        self.remove_piece_at(&Piece::Rook, &c_f, from_x - 4, from_y);
-                    }
-                }
-                else if p_f == Piece::Pawn && (to_x as i32 - from_x as i32).abs() == 1 {
-                    // En-passant zug ausführen
-                    self.remove_piece_at(&p_f, &c_f, from_x, from_y);
-                    match c_f {
-                        Color::White => self.remove_at(to_x, to_y + 1),
-                        Color::Black => self.remove_at(to_x, to_y - 1),
-                    }
-                    self.set_piece(&p_f, &c_f, to_x, to_y);
-                } else {
-                    self.set_piece(&p_f, &c_f, to_x, to_y);
-                    self.remove_piece_at(&p_f, &c_f, from_x, from_y);
-                }
+                self.remove_piece_at(&Piece::Rook, &c_f, to_x + 1, to_y);
+                self.set_piece(&Piece::Rook, &c_f, from_x - 4, from_y);
             }
+        }
 
-            // Update active player
-            self.active_player = match self.active_player {
-                Color::White => Color::Black,
-                Color::Black => Color::White,
-            } 
+        self.castle = undo.castle;
+        self.en_passant = undo.en_passant;
+        self.fifty_move = undo.fifty_move;
+        self.hash = undo.hash;
+        self.history.pop();
+    }
+
+    // Nullzug für die Suche: die Seite am Zug passt. Der Zähler für die 50-Züge-Regel wird
+    // zurückgesetzt, damit keine Wiederholung über den Nullzug hinweg erkannt wird
+    pub fn make_null_move(&mut self) -> NullMoveUndo {
+        let undo = NullMoveUndo {
+            en_passant: self.en_passant,
+            fifty_move: self.fifty_move,
+            hash: self.hash,
+        };
+        self.hash ^= ZOBRIST.en_passant(self.en_passant) ^ ZOBRIST.black_to_move();
+        self.en_passant = None;
+        self.fifty_move = 0;
+        self.active_player = self.opponent();
+        undo
+    }
+
+    pub fn unmake_null_move(&mut self, undo: NullMoveUndo) {
+        self.active_player = self.opponent();
+        self.en_passant = undo.en_passant;
+        self.fifty_move = undo.fifty_move;
+        self.hash = undo.hash;
+    }
+
+    // Ohne Leichtfiguren, Türme oder Damen droht Zugzwang
+    pub fn has_non_pawn_material(&self, c: &Color) -> bool {
+        let (_, queen, rooks, bishops, knights, _) = self.bitboards(c);
+        queen | rooks | bishops | knights != 0
+    }
+
+    fn opponent(&self) -> Color {
+        self.active_player.opposite()
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.fifty_move
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // Schlüssel von Grund auf berechnen, danach wird er nur noch inkrementell angepasst
+    fn compute_hash(&self) -> u64 {
+        let mut hash = ZOBRIST.castle(self.castle) ^ ZOBRIST.en_passant(self.en_passant);
+        if self.active_player == Color::Black {
+            hash ^= ZOBRIST.black_to_move();
+        }
+        for (c, p, x, y) in self.get_positions() {
+            hash ^= ZOBRIST.piece(&p, &c, x + 8 * y);
         }
+        hash
+    }
+
+    // Bewertungssummen von Grund auf berechnen, danach werden sie nur noch inkrementell angepasst
+    fn compute_eval(&mut self) {
+        (self.eval_mg, self.eval_eg, self.phase) = (0, 0, 0);
+        for (c, p, x, y) in self.get_positions() {
+            self.update_eval(&p, &c, x + 8 * y, 1);
+        }
+    }
+
+    // sign ist 1 beim Setzen und -1 beim Entfernen einer Figur
+    fn update_eval(&mut self, p: &Piece, c: &Color, square: u64, sign: i32) {
+        let (mg, eg) = self.piece_values.get(p, c, square);
+        let side = match c {
+            Color::White => sign,
+            Color::Black => -sign,
+        };
+        self.eval_mg += side * mg;
+        self.eval_eg += side * eg;
+        self.phase += sign * pst::phase_weight(p);
+    }
+
+    // Wie oft die aktuelle Stellung schon vorher auf dem Brett stand.
+    // Vor dem letzten Bauernzug oder Schlagen kann sie nicht vorgekommen sein
+    pub fn repetitions(&self) -> usize {
+        let key = self.hash;
+        let reversible = (self.fifty_move as usize).min(self.history.len());
+        self.history[self.history.len() - reversible..].iter().filter(|k| **k == key).count()
     }
 
     pub fn get_positions(&self) -> Vec<(Color, Piece, u64, u64)> {
@@ -315,32 +625,40 @@ impl Schach {
     }
 
     pub fn get_outcome(&self) -> Outcome {
-        if self.fifty_move >= 50 ||  0 == self.white_bishops + self.white_queen + self.white_rooks + self.white_pawns + self.black_bishops + self.black_queen + self.black_rooks + self.black_pawns {
-            return Outcome::Stalemate;
+        if self.is_insufficient_material() {
+            return Outcome::Draw(DrawReason::InsufficientMaterial);
         }
 
-        let attacked_squares = self.atacked_squares_bitmap();
-        let aktive_king = match self.active_player { Color::White => self.white_king, Color::Black => self.black_king};
-        let opponent = match self.active_player { Color::White => Color::Black, Color::Black => Color::White};
-        let is_check = attacked_squares & aktive_king != 0;
-        let mut legal_moves = 0;
-        for x in 0..8 {
-            for y in 0..8 {
-                legal_moves += self.get_legal_moves(x, y, 1).len();
-            }
-        }
-
-        if legal_moves == 0 {
-            if is_check {
-                Outcome::Checkmate(opponent)
+        // Ein Matt mit dem 100. Halbzug zählt noch als Matt
+        if !self.generate_moves(u64::MAX).into_iter().any(|m| self.is_legal(m)) {
+            if self.is_check() {
+                Outcome::Checkmate(self.opponent())
             } else {
-                Outcome::Stalemate
+                Outcome::Draw(DrawReason::Stalemate)
             }
+        } else if self.fifty_move >= 100 {
+            Outcome::Draw(DrawReason::FiftyMoveRule)
+        } else if self.repetitions() >= 2 {
+            Outcome::Draw(DrawReason::Repetition)
         } else {
             Outcome::None
         }
     }
 
+    // K gegen K, K und Leichtfigur gegen K, sowie nur Läufer auf Feldern der gleichen Farbe
+    pub fn is_insufficient_material(&self) -> bool {
+        if self.white_pawns | self.black_pawns | self.white_rooks | self.black_rooks | self.white_queen | self.black_queen != 0 {
+            return false;
+        }
+        let knights = self.white_knights | self.black_knights;
+        let bishops = self.white_bishops | self.black_bishops;
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+        let light_squares: u64 = 0b10101010_01010101_10101010_01010101_10101010_01010101_10101010_01010101;
+        knights == 0 && (bishops & light_squares == 0 || bishops & !light_squares == 0)
+    }
+
     fn log2(&self, x: u64) -> i32 {
         63 - x.leading_zeros() as i32
     }
@@ -353,210 +671,193 @@ impl Schach {
         (bin_log % 8, bin_log / 8)
     }
 
-    fn is_valid_move(&self, c: &Color, from_x: u64, from_y: u64, to_x: u64, to_y:u64) -> bool {
-        let mut brett = self.clone();
-        brett.move_piece(from_x, from_y, to_x, to_y);
-        let (king_x, king_y) = brett.get_king_pos(c);
-    
-        let dirs = [(0,1),(0,-1),(1,0),(-1,0),(1,1),(1,-1),(-1,-1),(-1,1)];
-        for (d_x,d_y) in dirs {
-            let mut x = king_x + d_x;
-            let mut y = king_y + d_y;
-            while x >= 0 && y >= 0 && x < 8 && y < 8 {
-                let moves = brett.get_legal_moves(x as u64, y as u64, 0);
-                if moves.contains(&(king_x, king_y)) {
-                    return false;
-                }
-                x += d_x;
-                y += d_y; 
-            }           
-        }
-        let dirs = [(2,1),(2,-1),(1,2),(-2,1),(-2,-1),(-1,2),(1,-2),(-1,-2)];
-        for (d_x,d_y) in dirs {
-            let x = king_x + d_x;
-            let y = king_y + d_y;
-            if x >= 0 && y >= 0 && x < 8 && y < 8 {
-                let moves = brett.get_legal_moves(x as u64, y as u64, 0);
-                if moves.contains(&(king_x, king_y)) {
-                    return false;
-                }
-            }           
-        }
-        true
+    // Prüft, ob der eigene König nach dem Zug angegriffen wäre, ohne den Zug auszuführen
+    pub fn is_legal(&self, m: Move) -> bool {
+        let from = 1 << m.from();
+        let to = 1 << m.to();
+        let captured = match m.is_en_passant() {
+            true => 1 << (m.to_xy().0 + 8 * m.from_xy().1),
+            false => to,
+        };
+        let occupied = (self.occupied() & !from & !captured) | to;
+        let (king, ..) = self.bitboards(&self.active_player);
+        let king_square = match king & from {
+            0 => king.trailing_zeros() as u64,
+            _ => m.to(),
+        };
+        self.attackers(king_square, &self.opponent(), occupied) & !captured == 0
+    }
+
+    pub fn is_check(&self) -> bool {
+        let (king, ..) = self.bitboards(&self.active_player);
+        self.is_attacked(king.trailing_zeros() as u64, &self.opponent())
     }
 
-    // Gibt eine bitmap der vom gegner attakierten Felder zurück
-    fn atacked_squares_bitmap(&self) -> u64 {
-        let mut brett = self.clone();
-        let mut bitmap = 0;
+    pub fn is_attacked(&self, square: u64, by: &Color) -> bool {
+        self.attackers(square, by, self.occupied()) != 0
+    }
 
-        match self.active_player {
-            Color::White => brett.active_player = Color::Black,
-            Color::Black => brett.active_player = Color::White,
-        }
+    // Alle Figuren von by, die square bei der Belegung occupied angreifen
+    fn attackers(&self, square: u64, by: &Color, occupied: u64) -> u64 {
+        let table = &*LOOKUP_TABLE;
+        let (king, queen, rooks, bishops, knights, pawns) = self.bitboards(by);
+        let defender = by.opposite();
+        (table.get_rook_moves(square, occupied) & (rooks | queen))
+            | (table.get_bishop_moves(square, occupied) & (bishops | queen))
+            | (table.get_knight_moves(square) & knights)
+            | (table.get_king_moves(square) & king)
+            | (table.get_pawn_attacks(square, &defender) & pawns)
+    }
 
-        for x in 0..8 {
-            for y in 0..8 {
-                let moves = brett.get_legal_moves(x, y, 0);
-                for (x_m,y_m) in moves {
-                    bitmap |= 1 << (x_m + 8 * y_m) as u64;
-                }
+    // Static Exchange Evaluation: Materialbilanz, wenn beide Seiten auf dem Zielfeld immer
+    // mit der billigsten Figur zurückschlagen und jederzeit aufhören dürfen
+    pub fn see(&self, m: Move) -> i32 {
+        // Ein König darf nur auf ungedeckte Felder schlagen
+        let value = |p: &Piece| if *p == Piece::King { 10_000 } else { piece_value(p) };
+        let to = m.to();
+        let mut occupied = self.occupied();
+        let mut gain = [0; 32];
+        gain[0] = match m.is_en_passant() {
+            true => {
+                occupied &= !(1 << (m.to_xy().0 + 8 * m.from_xy().1));
+                piece_value(&Piece::Pawn)
+            },
+            false => self.piece_on(to).map_or(0, |(p, _)| value(&p)),
+        };
+        let (mut from, mut side) = (m.from(), self.active_player.clone());
+        let mut piece = self.piece_on(from).map_or(Piece::Pawn, |(p, _)| p);
+        let mut d = 0;
+        loop {
+            d += 1;
+            gain[d] = value(&piece) - gain[d - 1];
+            if (-gain[d - 1]).max(gain[d]) < 0 || d == gain.len() - 1 {
+                break;
             }
+            occupied &= !(1 << from);
+            side = side.opposite();
+            let attackers = self.attackers(to, &side, occupied) & occupied;
+            if attackers == 0 {
+                break;
+            }
+            // Billigster Angreifer zuerst, Langschrittler dahinter werden durch occupied freigelegt
+            let (king, queen, rooks, bishops, knights, pawns) = self.bitboards(&side);
+            let candidates = [(pawns, Piece::Pawn), (knights, Piece::Knight), (bishops, Piece::Bishop), (rooks, Piece::Rook), (queen, Piece::Queen), (king, Piece::King)];
+            let (bitboard, p) = candidates.into_iter().find(|(bitboard, _)| bitboard & attackers != 0).unwrap();
+            from = (bitboard & attackers).trailing_zeros() as u64;
+            piece = p;
+        }
+        while d > 1 {
+            d -= 1;
+            gain[d - 1] = -(-gain[d - 1]).max(gain[d]);
+        }
+        gain[0]
+    }
+
+    // König, Dame, Türme, Läufer, Springer und Bauern eines Spielers
+    pub fn bitboards(&self, c: &Color) -> (u64, u64, u64, u64, u64, u64) {
+        match c {
+            Color::White => (self.white_king, self.white_queen, self.white_rooks, self.white_bishops, self.white_knights, self.white_pawns),
+            Color::Black => (self.black_king, self.black_queen, self.black_rooks, self.black_bishops, self.black_knights, self.black_pawns),
         }
-        bitmap
     }
 
-    pub fn get_legal_moves(&self, x: u64, y: u64, tiefe: u8) -> HashSet<(i32, i32)> {
-        let piece = self.get_piece_at(x, y);
-        if let Some((_,c)) = &piece {
-            if *c != self.active_player {
-                return HashSet::new();
+    pub fn pieces_of(&self, c: &Color) -> u64 {
+        let (king, queen, rooks, bishops, knights, pawns) = self.bitboards(c);
+        king | queen | rooks | bishops | knights | pawns
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.pieces_of(&Color::White) | self.pieces_of(&Color::Black)
+    }
+
+    pub fn get_legal_moves(&self, x: u64, y: u64) -> Vec<Move> {
+        self.generate_moves(1 << (x + 8 * y)).into_iter().filter(|m| self.is_legal(*m)).collect()
+    }
+
+    // Pseudolegale Züge der Figuren des aktiven Spielers auf den Feldern in from_mask
+    pub fn generate_moves(&self, from_mask: u64) -> Vec<Move> {
+        let table = &*LOOKUP_TABLE;
+        let mut moves = Vec::with_capacity(64);
+        let c = &self.active_player;
+        let (king, queen, rooks, bishops, knights, pawns) = self.bitboards(c);
+        let own = self.pieces_of(c);
+        let enemy = self.pieces_of(&self.opponent());
+        let occupied = own | enemy;
+
+        let add_targets = |from: u64, targets: u64, moves: &mut Vec<Move>| {
+            for to in squares(targets & !own) {
+                let flags = if enemy >> to & 1 == 1 { Move::CAPTURE } else { 0 };
+                moves.push(Move::new(from, to, None, flags));
             }
+        };
+        for from in squares(knights & from_mask) {
+            add_targets(from, table.get_knight_moves(from), &mut moves);
         }
-        match piece {
-            Some((Piece::Queen , c)) => self.generate_moves(&c, x, y, 7, tiefe, vec![(-1,-1), (-1,0), (-1,1), (0,-1), (0,1), (1,-1), (1,0), (1,1)]),
-            Some((Piece::Bishop, c)) => self.generate_moves(&c, x, y, 7, tiefe, vec![(-1,-1), (-1,1), (1,-1),  (1,1)]),
-            Some((Piece::Knight, c)) => self.generate_moves(&c, x, y, 1, tiefe, vec![(-1,-2), (-1,2), (1,-2), (1,2), (2,1), (2,-1), (-2,1), (-2,-1)]),
-            Some((Piece::Rook  , c)) => self.generate_moves(&c, x, y, 7, tiefe, vec![(-1,0), (0,-1), (0,1), (1,0)]),
-            Some((Piece::King  , c)) => {
-                let mut result = self.generate_moves(&c, x, y, 1, tiefe, vec![(-1,-1), (-1,0), (-1,1), (0,-1), (0,1), (1,-1), (1,0), (1,1)]);
-                if tiefe > 0 && self.castle >> (x + 8 * y) & 1 == 1 {
-                    let collision = self.white_bishops + self.white_knights + self.white_queen + self.white_rooks + self.white_pawns + self.black_bishops + self.black_knights + self.black_queen + self.black_rooks + self.black_pawns;
-                    match c {
-                        Color::White => {
-                            let path     = 0b00001110_00000000_00000000_00000000_00000000_00000000_00000000_00000000;
-                            let king_path:u64 = 0b00011100_00000000_00000000_00000000_00000000_00000000_00000000_00000000;
-                            let attacked = self.atacked_squares_bitmap();
-                            if  collision & path == 0 && king_path & attacked == 0 && self.castle >> (x - 4 + 8 * y) & 1 == 1 {
-                                if tiefe == 0 || self.is_valid_move(&c, x, y, x-2, y) {
-                                    result.insert((x as i32 - 2, y as i32));
-                                }
-                            }
-                            let path     = 0b01110000_00000000_00000000_00000000_00000000_00000000_00000000_00000000;
-                            if collision & path == 0 && path & attacked == 0 && self.castle >> (x + 3 + 8 * y) & 1 == 1 {
-                                if tiefe == 0 || self.is_valid_move(&c, x, y, x + 2, y) {
-                                    result.insert((x as i32 + 2, y as i32));
-                                }
-                            }
-                        },
-                        Color::Black => {
-                            let path     = 0b00000000_00000000_00000000_00000000_00000000_00000000_00000000_00001110;
-                            let king_path:u64 = 0b00000000_00000000_00000000_00000000_00000000_00000000_00000000_00011100;
-                            let attacked = self.atacked_squares_bitmap();
-                            if  collision & path == 0 && king_path & attacked == 0 && self.castle >> (x - 4 + 8 * y) & 1 == 1 {
-                                if tiefe == 0 || self.is_valid_move(&c, x, y, x - 2 , y) {
-                                    result.insert((x as i32 - 2, y as i32));
-                                }
-                            }
-                            let path     = 0b00000000_00000000_00000000_00000000_00000000_00000000_00000000_01110000;
-                            if collision & path == 0 && path & attacked == 0 && self.castle >> (x + 3 + 8 * y) & 1 == 1 {
-                                if tiefe == 0 || self.is_valid_move(&c, x, y, x, y) {
-                                    result.insert((x as i32 + 2, y as i32));
-                                }
-                            }
-                        },
-                    }
-                }
-                
-                result
-            },
-            Some((Piece::Pawn  , c)) => {
-                let mut result = HashSet::new();
-                let direction = match c { Color::White => -1, Color::Black => 1 };
-                let a = x as i32;
-                let mut b = y as i32 + direction;
-                if b < 8 && b >= 0 && self.get_piece_at(a as u64, b as u64).is_none() {
-                    if tiefe == 0 || self.is_valid_move(&c, x, y, a as u64, b as u64) {
-                        result.insert((a, b));
-                    }
-                    if (c == Color::White && y == 6) || (c == Color::Black && y == 1) {
-                        b += direction;
-                        if b < 8 && b >= 0 && self.get_piece_at(a as u64, b as u64).is_none() {
-                            if tiefe == 0 || self.is_valid_move(&c, x, y, a as u64, b as u64) {
-                                result.insert((a, b));
-                            }
-                        }
-                    }
-                }
-                for (a,b) in [(x as i32 + direction, y as i32 + direction), (x as i32 - direction, y as i32 + direction)] {
-                    if a >= 0 && a < 8 && b >= 0 && b < 8 {
-                        if let Some((_,c_d)) = self.get_piece_at(a as u64, b as u64) {
-                            if c != c_d {
-                                if tiefe == 0 || self.is_valid_move(&c, x, y, a as u64, b as u64) {
-                                    result.insert((a, b));
-                                }
-                            }
-                        }
-                    }
+        for from in squares(bishops & from_mask) {
+            add_targets(from, table.get_bishop_moves(from, occupied), &mut moves);
+        }
+        for from in squares(rooks & from_mask) {
+            add_targets(from, table.get_rook_moves(from, occupied), &mut moves);
+        }
+        for from in squares(queen & from_mask) {
+            add_targets(from, table.get_queen_moves(from, occupied), &mut moves);
+        }
+        for from in squares(king & from_mask) {
+            add_targets(from, table.get_king_moves(from), &mut moves);
+        }
+
+        // Rochade: König und Turm unbewegt, Felder dazwischen frei und der König zieht nicht durch Schach
+        if king & from_mask & self.castle != 0 {
+            let from = king.trailing_zeros() as u64;
+            let opponent = self.opponent();
+            for (rook, to, between, path) in [(from + 3, from + 2, 0b011u64 << (from + 1), [from, from + 1, from + 2]), (from - 4, from - 2, 0b0111u64 << (from - 3), [from, from - 1, from - 2])] {
+                if self.castle >> rook & 1 == 1 && rooks >> rook & 1 == 1 && occupied & between == 0 && path.iter().all(|s| !self.is_attacked(*s, &opponent)) {
+                    moves.push(Move::new(from, to, None, Move::CASTLE));
                 }
-                if let Some(pos) = self.en_passant {
-                    let b = match c { Color::White => y as i32 - 1, Color::Black => y as i32 + 1 };
-                    for a in [x as i32 + 1, x as i32 - 1] {
-                        if (a,b) == pos {
-                            if tiefe == 0 || self.is_valid_move(&c, x, y, a as u64, b as u64) {
-                                result.insert((a,b));
-                            }
-                        } 
-                    }
+            }
+        }
+
+        let (forward, start_rank, promotion_rank): (i64, u64, u64) = match c {
+            Color::White => (-8, 6, 0),
+            Color::Black => (8, 1, 7),
+        };
+        for from in squares(pawns & from_mask) {
+            let mut targets = Vec::new();
+            let one = (from as i64 + forward) as u64;
+            if occupied >> one & 1 == 0 {
+                targets.push((one, 0));
+                let two = (one as i64 + forward) as u64;
+                if from / 8 == start_rank && occupied >> two & 1 == 0 {
+                    targets.push((two, Move::DOUBLE_PUSH));
                 }
-                result
-            },
-            None => HashSet::new(),
-        }
-    }
-
-    // fn generate_moves_mogic(&self, x: u64, y: u64, tiefe: u8) -> HashSet<(i32, i32)> {
-    //     let all_pieces = self.white_king | self.white_queen | self.white_rooks | self.white_bishops | self.white_knights | self.white_pawns | self.black_king | self.black_queen | self.black_rooks | self.black_bishops | self.black_knights | self.black_pawns;
-    //     let mut rook_moves = (&*LOOKUP_TABLE).get_rook_moves(x + y * 8, all_pieces);
-    //     let friendly_pieces = match self.active_player {
-    //         Color::White => self.white_king | self.white_queen | self.white_rooks | self.white_bishops | self.white_knights | self.white_pawns,
-    //         Color::Black => self.black_king | self.black_queen | self.black_rooks | self.black_bishops | self.black_knights | self.black_pawns,
-    //     };
-    //     rook_moves = rook_moves & !friendly_pieces;
-
-
-    //     let mut result = HashSet::new();
-    //     let mut i: u64 = 0;
-    //     while rook_moves != 0 {
-    //         if rook_moves & 1 == 1 {
-    //             if tiefe == 0 || self.is_valid_move(&self.active_player, x, y, i % 8, i / 8) {
-    //                 result.insert((i as i32 % 8 , i as i32 / 8));
-    //             }
-    //         }
-    //         rook_moves >>= 1;
-    //         i += 1
-    //     }
-    //     result
-    // }
-
-    fn generate_moves(&self, c: &Color, x: u64, y:u64 ,range: u8, tiefe: u8, moves: Vec<(i32,i32)>) -> HashSet<(i32, i32)> {
-        let mut result = HashSet::new();
-        for m in moves {
-            let mut a = x as i32;
-            let mut b = y as i32;
-            for _ in 0..range {
-                a += m.0;
-                b += m.1;
-                if a >= 8 || b >= 8 || a < 0 || b < 0 {
-                    break;
+            }
+            for to in squares(table.get_pawn_attacks(from, c) & enemy) {
+                targets.push((to, Move::CAPTURE));
+            }
+            if let Some((x, y)) = self.en_passant {
+                let to = (x + 8 * y) as u64;
+                if table.get_pawn_attacks(from, c) >> to & 1 == 1 {
+                    targets.push((to, Move::CAPTURE | Move::EN_PASSANT));
                 }
-                let valid_move = tiefe == 0 || self.is_valid_move(&c, x, y, a as u64, b as u64);
-                if let Some((_, p_c)) = self.get_piece_at(a as u64, b as u64) {
-                    if p_c != *c && valid_move {
-                        result.insert((a, b));
+            }
+            for (to, flags) in targets {
+                if to / 8 == promotion_rank {
+                    for p in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+                        moves.push(Move::new(from, to, Some(p), flags));
                     }
-                    break;
-                } 
-                if valid_move {
-                    result.insert((a, b));
+                } else {
+                    moves.push(Move::new(from, to, None, flags));
                 }
-            }  
+            }
         }
-        result
+        moves
     }
-    
+
+    // Das Feld muss leer sein, sonst stimmt der Zobrist-Schlüssel nicht mehr
     fn set_piece(&mut self, p: &Piece, c: &Color, x: u64, y: u64) {        
         let position:u64 = 1 << (x + 8 * y);
+        self.hash ^= ZOBRIST.piece(p, c, x + 8 * y);
+        self.update_eval(p, c, x + 8 * y, 1);
     
         match (p, c) {
             (Piece::King, Color::White) =>   self.white_king |= position,
@@ -577,6 +878,8 @@ impl Schach {
     fn remove_at(&mut self ,x: u64,y: u64) {
         let position:u64 = 1 << (x + 8 * y);
         if let Some((p,c)) = self.get_piece_at(x, y) {
+            self.hash ^= ZOBRIST.piece(&p, &c, x + 8 * y);
+            self.update_eval(&p, &c, x + 8 * y, -1);
             match (p, c) {
                 (Piece::King, Color::White) =>   self.white_king -= position,
                 (Piece::King, Color::Black) =>   self.black_king -= position,
@@ -594,8 +897,11 @@ impl Schach {
         }
     }
 
+    // Die Figur muss auf dem Feld stehen
     fn remove_piece_at(&mut self , piece: &Piece, color: &Color,x: u64,y: u64) {
         let position:u64 = 1 << (x + 8 * y);
+        self.hash ^= ZOBRIST.piece(piece, color, x + 8 * y);
+        self.update_eval(piece, color, x + 8 * y, -1);
         match (piece, color) {
             (Piece::King, Color::White)   => self.white_king    &= !position,
             (Piece::King, Color::Black)   => self.black_king    &= !position,
@@ -613,6 +919,10 @@ impl Schach {
     }
 
 
+    pub fn piece_on(&self, square: u64) -> Option<(Piece, Color)> {
+        self.get_piece_at(square % 8, square / 8)
+    }
+
     fn get_piece_at(&self, x: u64, y: u64) -> Option<(Piece, Color)> {
         let pos = x + 8 * y;
         let pieces = [ (self.black_pawns, Piece::Pawn, Color::Black), (self.white_pawns, Piece::Pawn, Color::White), (self.black_bishops, Piece::Bishop, Color::Black), (self.white_bishops, Piece::Bishop, Color::White), (self.black_knights, Piece::Knight, Color::Black), (self.white_knights, Piece::Knight, Color::White), (self.black_rooks, Piece::Rook, Color::Black), (self.white_rooks, Piece::Rook, Color::White), (self.black_king, Piece::King, Color::Black), (self.white_king, Piece::King, Color::White), (self.black_queen, Piece::Queen, Color::Black), (self.white_queen, Piece::Queen, Color::White),];
@@ -624,4 +934,214 @@ impl Schach {
         }
         None
     }
-}
\ No newline at end of file
+}
+
+// Materialwert in Centipawns
+pub fn piece_value(p: &Piece) -> i32 {
+    match p {
+        Piece::King => 0,
+        Piece::Queen => 950,
+        Piece::Rook => 563,
+        Piece::Bishop => 333,
+        Piece::Knight => 305,
+        Piece::Pawn => 100,
+    }
+}
+
+// Iteriert über die Felder der gesetzten Bits
+pub fn squares(mut bitboard: u64) -> impl Iterator<Item = u64> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let square = bitboard.trailing_zeros() as u64;
+        bitboard &= bitboard - 1;
+        Some(square)
+    })
+}
+
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const FILE_H: u64 = FILE_A << 7;
+
+// Die Linien links und rechts von Linie x
+pub fn adjacent_files(x: u64) -> u64 {
+    let left = if x > 0 { FILE_A << (x - 1) } else { 0 };
+    let right = if x < 7 { FILE_A << (x + 1) } else { 0 };
+    left | right
+}
+
+// Alle Reihen vor Reihe y in Zugrichtung der Bauern dieser Farbe (y = 0 ist die achte Reihe)
+pub fn ranks_ahead(y: u64, color: &Color) -> u64 {
+    match color {
+        Color::White => (1 << (8 * y)) - 1,
+        Color::Black if y == 7 => 0,
+        Color::Black => u64::MAX << (8 * (y + 1)),
+    }
+}
+
+// Reihe aus Sicht der Farbe, 0 ist die eigene Grundreihe
+pub fn relative_rank(square: u64, color: &Color) -> usize {
+    match color {
+        Color::White => 7 - (square / 8) as usize,
+        Color::Black => (square / 8) as usize,
+    }
+}
+
+// Für Tests: Schlüssel, Bewertungssummen und Phase müssen nach jedem Zug denen einer frisch aus dem FEN
+// aufgebauten Stellung mit denselben Werten entsprechen, und unmake_move muss das Brett genau wiederherstellen
+#[cfg(test)]
+pub fn assert_consistent(brett: &mut Schach, depth: u32) {
+    let mut fresh = Schach::from_fen(&brett.to_fen()).unwrap();
+    fresh.set_piece_values(Arc::clone(&brett.piece_values));
+    assert_eq!(brett.hash(), fresh.hash(), "{}", brett.to_fen());
+    assert_eq!(brett.material_and_pst(), fresh.material_and_pst(), "{}", brett.to_fen());
+    assert_eq!(brett.phase(), fresh.phase(), "{}", brett.to_fen());
+    if depth == 0 {
+        return;
+    }
+    for m in brett.get_all_legal_moves() {
+        let before = brett.clone();
+        let undo = brett.make_move(m);
+        assert_consistent(brett, depth - 1);
+        brett.unmake_move(m, undo);
+        assert!(*brett == before, "{} nach {}", before.to_fen(), m);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            START,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 17 42",
+            "4k3/8/8/8/8/8/8/4K3 w - - 99 120",
+        ] {
+            assert_eq!(Schach::from_fen(fen).unwrap().to_fen(), fen);
+        }
+        assert!(Schach::from_fen(START).unwrap() == Schach::new());
+        assert_eq!(Schach::new().to_fen(), START);
+        // Ohne Zugzähler gelten die Startwerte
+        assert_eq!(Schach::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap().to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn fen_errors() {
+        let error = |fen: &str| Schach::from_fen(fen).err();
+        assert_eq!(error(""), Some(FenError::MissingField("piece placement")));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq"), Some(FenError::MissingField("en passant")));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1"), Some(FenError::InvalidPiece('X')));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1"), Some(FenError::InvalidRank(7)));
+        assert_eq!(error("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Some(FenError::InvalidRank(7)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/7/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Some(FenError::InvalidRank(3)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1"), Some(FenError::InvalidActivePlayer("x".to_string())));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1"), Some(FenError::InvalidCastling("KQkx".to_string())));
+        // Rochaderecht ohne Turm in der Ecke
+        assert_eq!(error("rnbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Some(FenError::InvalidCastling("KQkq".to_string())));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1"), Some(FenError::InvalidEnPassant("e3".to_string())));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq i6 0 1"), Some(FenError::InvalidEnPassant("i6".to_string())));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1"), Some(FenError::InvalidCounter("x".to_string())));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0"), Some(FenError::InvalidCounter("0".to_string())));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQQBNR w kq - 0 1"), Some(FenError::MissingKing(Color::White)));
+    }
+
+    fn play(brett: &mut Schach, moves: &[&str]) {
+        for name in moves {
+            let m = brett.get_all_legal_moves().into_iter().find(|m| m.to_string() == *name).unwrap();
+            brett.make_move(m);
+        }
+    }
+
+    #[test]
+    fn threefold_repetition() {
+        let knights = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut brett = Schach::new();
+        play(&mut brett, &knights);
+        assert_eq!(brett.repetitions(), 1);
+        assert_eq!(brett.get_outcome(), Outcome::None);
+        play(&mut brett, &knights);
+        assert_eq!(brett.repetitions(), 2);
+        assert_eq!(brett.get_outcome(), Outcome::Draw(DrawReason::Repetition));
+
+        // Nach einem Bauernzug zählen die Stellungen davor nicht mehr
+        let mut brett = Schach::new();
+        play(&mut brett, &knights);
+        play(&mut brett, &["e2e3", "e7e6"]);
+        assert_eq!(brett.repetitions(), 0);
+        play(&mut brett, &knights);
+        assert_eq!(brett.repetitions(), 1);
+        play(&mut brett, &knights);
+        assert_eq!(brett.get_outcome(), Outcome::Draw(DrawReason::Repetition));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let outcome = |fen: &str| Schach::from_fen(fen).unwrap().get_outcome();
+        let insufficient = Outcome::Draw(DrawReason::InsufficientMaterial);
+        assert_eq!(outcome("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), insufficient);
+        assert_eq!(outcome("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1"), insufficient);
+        // Läufer auf c1 und f8 stehen beide auf dunklen Feldern
+        assert_eq!(outcome("2k2b2/8/8/8/8/8/8/2B1K3 w - - 0 1"), insufficient);
+        // Läufer auf c1 und e8 stehen auf verschiedenfarbigen Feldern, ein Matt ist möglich
+        assert_eq!(outcome("2k1b3/8/8/8/8/8/8/2B1K3 w - - 0 1"), Outcome::None);
+        assert_eq!(outcome("4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1"), Outcome::None);
+        assert_eq!(outcome("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"), Outcome::None);
+        assert_eq!(outcome("4k3/8/8/8/8/8/P7/4K3 w - - 0 1"), Outcome::None);
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        let mut brett = Schach::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert_eq!(brett.get_outcome(), Outcome::None);
+        play(&mut brett, &["a1a2"]);
+        assert_eq!(brett.get_outcome(), Outcome::Draw(DrawReason::FiftyMoveRule));
+        // Ein Matt mit dem 100. Halbzug zählt noch als Matt
+        let mut brett = Schach::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80").unwrap();
+        play(&mut brett, &["a1a8"]);
+        assert_eq!(brett.get_outcome(), Outcome::Checkmate(Color::White));
+    }
+
+    #[test]
+    fn underpromotion() {
+        let brett = Schach::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut names: Vec<String> = brett.get_all_legal_moves().iter()
+            .map(|m| m.to_string())
+            .filter(|name| name.starts_with("a7"))
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a7a8b", "a7a8n", "a7a8q", "a7a8r", "a7b8b", "a7b8n", "a7b8q", "a7b8r"]);
+
+        let mut springer = brett.clone();
+        play(&mut springer, &["a7b8n"]);
+        assert_eq!(springer.to_fen(), "1N2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        let mut turm = brett.clone();
+        play(&mut turm, &["a7a8r"]);
+        assert_eq!(turm.to_fen(), "Rn2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    #[should_panic(expected = "no piece on the start square")]
+    fn move_from_an_empty_square() {
+        // e4 ist in der Grundstellung leer
+        Schach::new().make_move(Move::new(4 + 8 * 4, 4 + 8 * 3, None, 0));
+    }
+
+    #[test]
+    fn make_unmake_round_trip() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 17 42",
+        ] {
+            assert_consistent(&mut Schach::from_fen(fen).unwrap(), 1);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+use crate::search::MAX_DEPTH;
+
+// Bewertung in Centipawns. Matts liegen nahe bei ±MATE, der Abstand gibt die Halbzüge bis zum Matt an
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Score(pub i32);
+
+impl Score {
+    pub const DRAW: Score = Score(0);
+    pub const MATE: Score = Score(30_000);
+    pub const INFINITE: Score = Score(31_000);
+    // Alles darüber ist ein Matt in höchstens MAX_DEPTH Halbzügen
+    const MATE_BOUND: i32 = Score::MATE.0 - MAX_DEPTH as i32;
+
+    // Die Seite am Zug setzt in ply Halbzügen matt
+    pub fn mate_in(ply: usize) -> Score {
+        debug_assert!(ply <= MAX_DEPTH as usize);
+        Score(Score::MATE.0 - ply as i32)
+    }
+
+    // Die Seite am Zug ist nach ply Halbzügen matt
+    pub fn mated_in(ply: usize) -> Score {
+        debug_assert!(ply <= MAX_DEPTH as usize);
+        Score(-Score::MATE.0 + ply as i32)
+    }
+
+    pub fn is_mate(self) -> bool {
+        self.0.abs() >= Score::MATE_BOUND
+    }
+
+    // Züge (nicht Halbzüge) bis zum Matt, negativ wenn die Seite am Zug matt gesetzt wird
+    pub fn mate_moves(self) -> Option<i32> {
+        match self.0 {
+            s if s >= Score::MATE_BOUND => Some((Score::MATE.0 - s + 1) / 2),
+            s if s <= -Score::MATE_BOUND => Some(-(Score::MATE.0 + s) / 2),
+            _ => None,
+        }
+    }
+
+    // Die Tabelle speichert Matts relativ zur Stellung, nicht zur Wurzel, sonst stimmt
+    // der Abstand nicht mehr, wenn die Stellung in einer anderen Tiefe wieder auftaucht
+    pub fn to_tt(self, ply: usize) -> Score {
+        match self.0 {
+            s if s >= Score::MATE_BOUND => Score(s + ply as i32),
+            s if s <= -Score::MATE_BOUND => Score(s - ply as i32),
+            s => Score(s),
+        }
+    }
+
+    pub fn from_tt(self, ply: usize) -> Score {
+        match self.0 {
+            s if s >= Score::MATE_BOUND => Score(s - ply as i32),
+            s if s <= -Score::MATE_BOUND => Score(s + ply as i32),
+            s => Score(s),
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score(self.0 + other.0)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score(self.0 - other.0)
+    }
+}
+
+// Bauerneinheiten wie "+0.35" oder Matt in Zügen wie "M3" bzw. "-M2"
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mate_moves() {
+            Some(moves) if moves > 0 => write!(f, "M{}", moves),
+            Some(moves) => write!(f, "-M{}", -moves),
+            None => write!(f, "{:+.2}", self.0 as f32 / 100.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(Score(35).to_string(), "+0.35");
+        assert_eq!(Score(-120).to_string(), "-1.20");
+        assert_eq!(Score::mate_in(1).to_string(), "M1");
+        assert_eq!(Score::mate_in(5).to_string(), "M3");
+        assert_eq!(Score::mated_in(4).to_string(), "-M2");
+        assert_eq!(Score::mated_in(0).to_string(), "-M0");
+    }
+
+    #[test]
+    fn tt_adjustment_round_trip() {
+        for score in [Score(42), Score::mate_in(7), Score::mated_in(6)] {
+            assert_eq!(score.to_tt(3).from_tt(3), score);
+        }
+        // Matt in 7 ab der Wurzel ist von einer Stellung bei Ply 3 aus ein Matt in 4
+        assert_eq!(Score::mate_in(7).to_tt(3), Score::mate_in(4));
+        assert_eq!(Score::mate_in(4).from_tt(5), Score::mate_in(9));
+    }
+}
@@ -0,0 +1,949 @@
+use std::fmt;
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+
+use crate::evaluation::{Evaluator, HandCrafted, HAND_CRAFTED};
+use crate::movepick::{Heuristics, MovePicker};
+use crate::moves::Move;
+use crate::schach::{piece_value, Color, Piece, Schach};
+use crate::score::Score;
+use crate::transposition_table::{Bound, TranspositionTable};
+
+pub const MAX_DEPTH: u32 = 64;
+// Sicherheitsabstand beim Delta Pruning der Ruhesuche, in Centipawns
+const DELTA_MARGIN: i32 = 200;
+// Reverse Futility Pruning pro verbleibender Tiefe
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+// Futility Pruning bei Tiefe 1 und 2
+const FUTILITY_MARGINS: [i32; 3] = [0, 200, 350];
+// Halbe Breite des ersten Aspirationsfensters, wird bei jedem Fehlschlag verdoppelt
+const ASPIRATION_WINDOW: i32 = 25;
+
+// Wie lange bzw. wie tief gesucht wird, angelehnt an die Parameter von "go" in UCI.
+// Ohne Angaben wird bis MAX_DEPTH gesucht
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Duration,
+    pub binc: Duration,
+    pub movestogo: Option<u32>,
+    pub nodes: Option<u64>,
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        SearchLimits { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        SearchLimits { movetime: Some(movetime), ..Default::default() }
+    }
+
+    pub fn infinite() -> Self {
+        SearchLimits { infinite: true, ..Default::default() }
+    }
+
+    // Zeit für den aktuellen Zug, None heißt ohne Zeitbegrenzung
+    pub fn time_budget(&self, side: &Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime);
+        }
+        let (time, inc) = match side {
+            Color::White => (self.wtime?, self.winc),
+            Color::Black => (self.btime?, self.binc),
+        };
+        // Ohne Angabe wird mit 30 weiteren Zügen gerechnet
+        let moves_to_go = self.movestogo.unwrap_or(30).max(1);
+        let budget = time / moves_to_go + inc * 3 / 4;
+        // Etwas Puffer, damit die Uhr nicht abläuft
+        Some(budget.min(time.saturating_sub(Duration::from_millis(50))))
+    }
+}
+
+// Einstellungen, die nicht von der Bedenkzeit abhängen. Die Pruning-Verfahren lassen sich
+// einzeln abschalten, z.B. um ihren Einfluss zu messen
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    // Bei gleich bewerteten Zügen an der Wurzel zufällig wählen
+    pub random_tie_break: bool,
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub reverse_futility: bool,
+    pub futility: bool,
+    pub check_extensions: bool,
+    // Principal Variation Search: nur der erste Zug wird mit vollem Fenster durchsucht
+    pub pvs: bool,
+    // Jede Iteration ab Tiefe 4 beginnt mit einem engen Fenster um den letzten Wert
+    pub aspiration_windows: bool,
+    // Anzahl der Suchthreads einschließlich des Hauptthreads
+    pub threads: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            random_tie_break: false,
+            null_move: true,
+            late_move_reductions: true,
+            reverse_futility: true,
+            futility: true,
+            check_extensions: true,
+            pvs: true,
+            aspiration_windows: true,
+            threads: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    // None, wenn die Seite am Zug matt oder patt ist
+    pub best_move: Option<Move>,
+    // Aus Sicht der Seite am Zug
+    pub score: Score,
+    // Tiefe der letzten vollständigen Iteration
+    pub depth: u32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub pv: Vec<Move>,
+    pub stats: SearchStats,
+}
+
+// Zwischenstand nach jeder vollständigen Iteration, z.B. für "info" in UCI
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: u32,
+    // Größte erreichte Tiefe einschließlich Ruhesuche und Verlängerungen
+    pub seldepth: u32,
+    // Aus Sicht der Seite am Zug
+    pub score: Score,
+    pub nodes: u64,
+    pub nps: u64,
+    // Füllstand der Tabelle in Promille
+    pub hashfull: u32,
+    pub elapsed: Duration,
+    pub pv: Vec<Move>,
+}
+
+// Wie oft Suchen mit engem Fenster wiederholt werden mussten, summiert über alle Threads
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    pub null_window_searches: u64,
+    pub pvs_researches: u64,
+    pub aspiration_searches: u64,
+    pub aspiration_fail_lows: u64,
+    pub aspiration_fail_highs: u64,
+}
+
+impl AddAssign<&SearchStats> for SearchStats {
+    fn add_assign(&mut self, other: &SearchStats) {
+        self.null_window_searches += other.null_window_searches;
+        self.pvs_researches += other.pvs_researches;
+        self.aspiration_searches += other.aspiration_searches;
+        self.aspiration_fail_lows += other.aspiration_fail_lows;
+        self.aspiration_fail_highs += other.aspiration_fail_highs;
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pvs {}/{}, aspiration {}+{}/{}", self.pvs_researches, self.null_window_searches,
+            self.aspiration_fail_lows, self.aspiration_fail_highs, self.aspiration_searches)
+    }
+}
+
+// Zustand einer laufenden Suche, wird von allen Threads geteilt
+struct SearchState<'a, E> {
+    tt: &'a TranspositionTable,
+    evaluator: &'a E,
+    options: &'a SearchOptions,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    nodes: AtomicU64,
+    seldepth: AtomicUsize,
+    stopped: &'a AtomicBool,
+    on_info: &'a (dyn Fn(&SearchInfo) + Sync),
+}
+
+// Zustand eines einzelnen Suchthreads. Knoten werden lokal gezählt und nur alle 1024 Knoten in die
+// gemeinsamen Zähler übertragen, damit sich die Threads nicht bei jedem Knoten um dieselbe Cache-Zeile streiten
+struct ThreadState {
+    heuristics: Heuristics,
+    // Noch nicht übertragene Knoten
+    pending_nodes: u64,
+    // Gemeinsamer Zählerstand bei der letzten Übertragung
+    shared_nodes: u64,
+    seldepth: usize,
+    stopped: bool,
+    stats: SearchStats,
+}
+
+impl ThreadState {
+    fn new() -> Self {
+        ThreadState {
+            heuristics: Heuristics::new(),
+            pending_nodes: 0,
+            shared_nodes: 0,
+            seldepth: 0,
+            stopped: false,
+            stats: SearchStats::default(),
+        }
+    }
+
+    // Zeit und stop nur alle 1024 Knoten prüfen, Instant::now ist vergleichsweise teuer
+    fn count_node<E>(&mut self, state: &SearchState<E>, ply: usize) -> bool {
+        self.seldepth = self.seldepth.max(ply);
+        self.pending_nodes += 1;
+        if state.node_limit.is_some_and(|limit| self.shared_nodes + self.pending_nodes >= limit) {
+            state.stopped.store(true, Ordering::Relaxed);
+            self.stopped = true;
+        }
+        if self.pending_nodes == 1024 {
+            self.flush(state);
+            if state.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                state.stopped.store(true, Ordering::Relaxed);
+            }
+            self.stopped = state.stopped.load(Ordering::Relaxed);
+        }
+        self.stopped
+    }
+
+    fn flush<E>(&mut self, state: &SearchState<E>) {
+        self.shared_nodes = state.nodes.fetch_add(self.pending_nodes, Ordering::Relaxed) + self.pending_nodes;
+        self.pending_nodes = 0;
+        state.seldepth.fetch_max(self.seldepth, Ordering::Relaxed);
+    }
+}
+
+// Eine Suche im Hintergrund. Nach stop() endet sie nach wenigen Knoten mit dem besten Zug der letzten
+// vollständigen Iteration. Wird das Handle verworfen, wird die Suche abgebrochen und abgewartet,
+// on_finish wird dann nicht mehr aufgerufen, damit kein veraltetes Ergebnis mehr ankommt
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+    discarded: Arc<AtomicBool>,
+    thread: Option<JoinHandle<SearchResult>>,
+}
+
+impl SearchHandle {
+    pub fn start(brett: Schach, limits: SearchLimits, options: SearchOptions, tt: Arc<TranspositionTable>) -> Self {
+        Self::start_with(brett, limits, options, tt, Arc::new(HandCrafted::default()), |_| (), |_| ())
+    }
+
+    // on_info und on_finish laufen im Suchthread, z.B. um in UCI "info" und "bestmove" auszugeben
+    pub fn start_with<E: Evaluator + Send + 'static>(
+        brett: Schach,
+        limits: SearchLimits,
+        options: SearchOptions,
+        tt: Arc<TranspositionTable>,
+        evaluator: Arc<E>,
+        on_info: impl Fn(&SearchInfo) + Send + Sync + 'static,
+        on_finish: impl FnOnce(&SearchResult) + Send + 'static,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let discarded = Arc::new(AtomicBool::new(false));
+        let (thread_stop, thread_discarded) = (Arc::clone(&stop), Arc::clone(&discarded));
+        let thread = thread::spawn(move || {
+            let result = brett.search_with(&limits, &options, &tt, &*evaluator, &thread_stop, &on_info);
+            if !thread_discarded.load(Ordering::Relaxed) {
+                on_finish(&result);
+            }
+            result
+        });
+        SearchHandle { stop, discarded, thread: Some(thread) }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|thread| thread.is_finished())
+    }
+
+    pub fn wait(mut self) -> SearchResult {
+        self.thread.take().unwrap().join().expect("search thread panicked")
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        self.discarded.store(true, Ordering::Relaxed);
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Schach {
+    pub fn search(&self, limits: &SearchLimits, options: &SearchOptions, tt: &TranspositionTable) -> SearchResult {
+        self.search_with(limits, options, tt, &*HAND_CRAFTED, &AtomicBool::new(false), &|_| ())
+    }
+
+    // Lazy SMP: alle Threads durchsuchen denselben Baum und teilen sich nur die Tabelle.
+    // Das Ergebnis kommt vom Hauptthread, die Helfer füllen die Tabelle mit Werten aus anderen Teilbäumen.
+    // Wird stop gesetzt, bricht die Suche ab. on_info wird nach jeder Iteration des Hauptthreads aufgerufen
+    pub fn search_with<E: Evaluator>(&self, limits: &SearchLimits, options: &SearchOptions, tt: &TranspositionTable, evaluator: &E, stop: &AtomicBool, on_info: &(dyn Fn(&SearchInfo) + Sync)) -> SearchResult {
+        let start = Instant::now();
+        // Ohne legale Züge gibt es nichts zu suchen
+        if self.get_all_legal_moves().is_empty() {
+            return SearchResult {
+                best_move: None,
+                score: match self.is_check() {
+                    true => Score::mated_in(0),
+                    false => Score::DRAW,
+                },
+                depth: 0,
+                nodes: 0,
+                elapsed: start.elapsed(),
+                pv: Vec::new(),
+                stats: SearchStats::default(),
+            };
+        }
+        let budget = limits.time_budget(&self.active_player);
+        let state = SearchState {
+            tt,
+            evaluator,
+            options,
+            deadline: budget.map(|budget| start + budget),
+            node_limit: limits.nodes,
+            nodes: AtomicU64::new(0),
+            seldepth: AtomicUsize::new(0),
+            stopped: stop,
+            on_info,
+        };
+        tt.new_search();
+
+        let (best_move, best_eval, completed, stats) = std::thread::scope(|scope| {
+            let helpers: Vec<_> = (1..options.threads.max(1)).map(|thread| {
+                let state = &state;
+                scope.spawn(move || self.iterative_deepening(limits, state, thread, start, budget))
+            }).collect();
+            let (best_move, best_eval, completed, mut stats) = self.iterative_deepening(limits, &state, 0, start, budget);
+            // Die Helfer werden nicht mehr gebraucht
+            state.stopped.store(true, Ordering::Relaxed);
+            for helper in helpers {
+                stats += &helper.join().expect("search thread panicked").3;
+            }
+            (best_move, best_eval, completed, stats)
+        });
+
+        SearchResult {
+            best_move: Some(best_move),
+            score: best_eval,
+            depth: completed,
+            nodes: state.nodes.load(Ordering::Relaxed),
+            elapsed: start.elapsed(),
+            pv: self.principal_variation(best_move, completed as usize, tt),
+            stats,
+        }
+    }
+
+    fn iterative_deepening<E: Evaluator>(&self, limits: &SearchLimits, state: &SearchState<E>, thread: usize, start: Instant, budget: Option<Duration>) -> (Move, Score, u32, SearchStats) {
+        let mut brett = self.clone();
+        state.evaluator.prepare(&mut brett);
+        let mut thread_state = ThreadState::new();
+        let mut root_moves = self.get_all_legal_moves();
+        let mut best_move = root_moves[0];
+        let mut best_eval = Score::DRAW;
+        let mut completed = 0;
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+        // Jeder zweite Helfer ist eine Iteration voraus, damit sich die Threads nicht nur gegenseitig nachrechnen
+        let mut depth = 1 + (thread % 2) as u32;
+        while depth <= max_depth {
+            // Der beste Zug der letzten Iteration wird zuerst durchsucht und gewinnt bei Gleichstand
+            if let Some(i) = root_moves.iter().position(|m| *m == best_move) {
+                root_moves[..=i].rotate_right(1);
+            }
+            // Aspirationsfenster: der Wert ändert sich meist nur wenig zur letzten Iteration. Fällt er aus
+            // dem Fenster, wird auf der betroffenen Seite erweitert und wiederholt
+            let mut delta = Score(ASPIRATION_WINDOW);
+            let (mut alpha, mut beta) = match state.options.aspiration_windows && depth >= 4 && !best_eval.is_mate() {
+                true => (best_eval - delta, best_eval + delta),
+                false => (-Score::INFINITE, Score::INFINITE),
+            };
+            let (eval, m) = loop {
+                if beta < Score::INFINITE || alpha > -Score::INFINITE {
+                    thread_state.stats.aspiration_searches += 1;
+                }
+                // Eine abgebrochene Iteration ist unvollständig, es gilt das Ergebnis der letzten
+                let Some((eval, m)) = brett.search_root(&root_moves, depth, alpha, beta, state, &mut thread_state) else {
+                    thread_state.flush(state);
+                    return (best_move, best_eval, completed, thread_state.stats);
+                };
+                if eval <= alpha && alpha > -Score::INFINITE {
+                    thread_state.stats.aspiration_fail_lows += 1;
+                    alpha = (alpha - delta).max(-Score::INFINITE);
+                } else if eval >= beta && beta < Score::INFINITE {
+                    thread_state.stats.aspiration_fail_highs += 1;
+                    beta = (beta + delta).min(Score::INFINITE);
+                } else {
+                    break (eval, m);
+                }
+                delta = delta + delta;
+            };
+            (best_eval, best_move) = (eval, m);
+            completed = depth;
+            state.tt.store(self.hash(), depth as u8, best_eval, Bound::Exact, Some(best_move));
+            thread_state.flush(state);
+            if thread == 0 {
+                (state.on_info)(&self.search_info(depth, best_eval, best_move, state, start));
+            }
+
+            // Die nächste Iteration dauert meist länger als alle bisherigen zusammen
+            if thread == 0 && budget.is_some_and(|budget| start.elapsed() > budget / 2) {
+                break;
+            }
+            depth += 1;
+        }
+        thread_state.flush(state);
+        (best_move, best_eval, completed, thread_state.stats)
+    }
+
+    fn search_info<E: Evaluator>(&self, depth: u32, score: Score, best_move: Move, state: &SearchState<E>, start: Instant) -> SearchInfo {
+        let nodes = state.nodes.load(Ordering::Relaxed);
+        let elapsed = start.elapsed();
+        SearchInfo {
+            depth,
+            seldepth: (state.seldepth.load(Ordering::Relaxed) as u32).max(depth),
+            score,
+            nodes,
+            nps: (nodes as u128 * 1_000_000 / elapsed.as_micros().max(1)) as u64,
+            hashfull: state.tt.hashfull(),
+            elapsed,
+            pv: self.principal_variation(best_move, depth as usize, state.tt),
+        }
+    }
+
+    // Liegt der Wert nicht innerhalb von alpha und beta, ist er nur eine Schranke.
+    // Bei einem Wert über beta wird sofort abgebrochen
+    fn search_root<E: Evaluator>(&mut self, root_moves: &[Move], depth: u32, window_alpha: Score, beta: Score, state: &SearchState<E>, thread: &mut ThreadState) -> Option<(Score, Move)> {
+        let mut best_eval = -Score::INFINITE;
+        let mut equal = Vec::new();
+        for (i, m) in root_moves.iter().enumerate() {
+            // Für den Zufall zwischen gleich guten Zügen muss auch ein gleicher Wert genau bekannt sein
+            let alpha = match state.options.random_tie_break && !equal.is_empty() {
+                true => best_eval - Score(1),
+                false => best_eval,
+            }.max(window_alpha);
+            let undo = self.make_move(*m);
+            let eval = self.search_child(depth - 1, 1, i, alpha, beta, state, thread);
+            self.unmake_move(*m, undo);
+            if state.stopped.load(Ordering::Relaxed) {
+                return None;
+            }
+            if eval >= beta {
+                return Some((eval, *m));
+            }
+            if eval > best_eval {
+                best_eval = eval;
+                equal = vec![*m];
+            } else if eval == best_eval {
+                equal.push(*m);
+            }
+        }
+        // Zufall nur zwischen gleich guten Zügen, damit die Partien abwechslungsreicher werden
+        let best_move = match state.options.random_tie_break {
+            true => *equal.choose(&mut thread_rng())?,
+            false => equal[0],
+        };
+        Some((best_eval, best_move))
+    }
+
+    // Folgt den besten Zügen aus der Tabelle, bis ein Eintrag fehlt, nicht passt oder sich die Stellung wiederholt
+    fn principal_variation(&self, first: Move, max_len: usize, tt: &TranspositionTable) -> Vec<Move> {
+        let mut brett = self.clone();
+        brett.make_move(first);
+        let mut pv = vec![first];
+        while pv.len() < max_len && brett.repetitions() == 0 {
+            let Some(m) = tt.probe(brett.hash()).and_then(|entry| entry.best_move) else {
+                break;
+            };
+            if !brett.get_all_legal_moves().contains(&m) {
+                break;
+            }
+            brett.make_move(m);
+            pv.push(m);
+        }
+        pv
+    }
+
+    // Negamax mit Alpha-Beta, der Wert ist aus Sicht der Seite am Zug. ply zählt die Halbzüge ab der Wurzel,
+    // null_allowed verhindert zwei Nullzüge hintereinander
+    #[allow(clippy::too_many_arguments)]
+    fn negamax<E: Evaluator>(&mut self, mut depth: u32, ply: usize, mut alpha: Score, beta: Score, state: &SearchState<E>, thread: &mut ThreadState, null_allowed: bool) -> Score {
+        // Eine Wiederholung innerhalb der Suche wird schon beim zweiten Auftreten als Remis gewertet
+        if self.repetitions() >= 1 || self.is_insufficient_material() {
+            return Score::DRAW;
+        }
+        let in_check = self.is_check();
+        // Ein Matt mit dem 100. Halbzug zählt noch als Matt
+        if self.halfmove_clock() >= 100 {
+            return match in_check && self.get_all_legal_moves().is_empty() {
+                true => Score::mated_in(ply),
+                false => Score::DRAW,
+            };
+        }
+        if ply >= MAX_DEPTH as usize {
+            return self.static_eval(state.evaluator);
+        }
+        // Schachgebote werden nicht im Horizont versteckt
+        if in_check && state.options.check_extensions {
+            depth += 1;
+        }
+        if depth == 0 {
+            return self.quiescence(ply, alpha, beta, state, thread);
+        }
+        if thread.count_node(state, ply) {
+            return Score::DRAW;
+        }
+        let key = self.hash();
+        let mut hash_move = None;
+        if let Some(entry) = state.tt.probe(key) {
+            hash_move = entry.best_move;
+            let score = entry.score.from_tt(ply);
+            // Werte aus Cutoffs sind nur Schranken und dürfen nur außerhalb des Fensters verwendet werden
+            if entry.depth as u32 >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        let no_mate_window = !alpha.is_mate() && !beta.is_mate();
+        let static_eval = match in_check {
+            true => -Score::INFINITE,
+            false => self.static_eval(state.evaluator),
+        };
+
+        // Reverse Futility: selbst mit Abzug liegt die Stellung so klar über beta, dass nicht weitergesucht wird
+        if state.options.reverse_futility && !in_check && no_mate_window && depth <= 3
+            && static_eval - Score(REVERSE_FUTILITY_MARGIN * depth as i32) >= beta {
+            return static_eval;
+        }
+
+        // Nullzug: reicht es sogar, wenn der Gegner zweimal zieht, wird geschnitten.
+        // Nur mit Figuren auf dem Brett, in reinen Bauernendspielen ist Zugzwang häufig
+        if state.options.null_move && null_allowed && !in_check && no_mate_window && depth >= 3
+            && static_eval >= beta && self.has_non_pawn_material(&self.active_player) {
+            let reduction = 2 + depth / 4;
+            let undo = self.make_null_move();
+            let eval = -self.negamax(depth.saturating_sub(1 + reduction), ply + 1, -beta, -beta + Score(1), state, thread, false);
+            self.unmake_null_move(undo);
+            if state.stopped.load(Ordering::Relaxed) {
+                return Score::DRAW;
+            }
+            if eval >= beta {
+                return beta;
+            }
+        }
+
+        // Futility: kurz vor dem Horizont bringen ruhige Züge alpha nicht mehr in Reichweite
+        let futile = state.options.futility && !in_check && no_mate_window && depth <= 2
+            && static_eval + Score(FUTILITY_MARGINS[depth as usize]) <= alpha;
+
+        let mut picker = MovePicker::new(self, hash_move, thread.heuristics.killers(ply));
+        let original_alpha = alpha;
+        let mut best_eval = -Score::INFINITE;
+        let mut best_move = None;
+        let mut moves_searched = 0;
+        while let Some(m) = picker.next(self, &thread.heuristics) {
+            let quiet = !m.is_capture() && m.promotion().is_none();
+            let undo = self.make_move(m);
+            let gives_check = self.is_check();
+            if futile && quiet && !gives_check && best_move.is_some() {
+                self.unmake_move(m, undo);
+                best_eval = best_eval.max(static_eval + Score(FUTILITY_MARGINS[depth as usize]));
+                continue;
+            }
+
+            // Late Move Reductions: späte ruhige Züge erst flacher durchsuchen, nur wenn sie alpha
+            // verbessern folgt die volle Tiefe
+            let reduction = match state.options.late_move_reductions && moves_searched >= 3 && depth >= 3 && quiet && !in_check && !gives_check {
+                true if moves_searched >= 6 && depth >= 6 => 2,
+                true => 1,
+                false => 0,
+            };
+            let eval = self.search_child(depth - 1 - reduction, ply + 1, moves_searched, alpha, beta, state, thread);
+            let eval = match reduction > 0 && eval > alpha && !state.stopped.load(Ordering::Relaxed) {
+                true => self.search_child(depth - 1, ply + 1, moves_searched, alpha, beta, state, thread),
+                false => eval,
+            };
+            self.unmake_move(m, undo);
+            moves_searched += 1;
+            // Nach dem Abbruch sind alle Werte unbrauchbar und dürfen nicht in die Tabelle
+            if state.stopped.load(Ordering::Relaxed) {
+                return Score::DRAW;
+            }
+            if eval > best_eval {
+                best_eval = eval;
+                best_move = Some(m);
+            }
+            alpha = alpha.max(eval);
+            if alpha >= beta {
+                if quiet {
+                    thread.heuristics.update(m, ply, depth);
+                }
+                break;
+            }
+        }
+        // Kein legaler Zug: Matt oder Patt
+        if best_move.is_none() {
+            return match in_check {
+                true => Score::mated_in(ply),
+                false => Score::DRAW,
+            };
+        }
+
+        let bound = if best_eval >= beta {
+            Bound::Lower
+        } else if best_eval <= original_alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        state.tt.store(key, depth as u8, best_eval.to_tt(ply), bound, best_move);
+        best_eval
+    }
+
+    // Durchsucht die Stellung nach einem Zug aus Sicht des Ziehenden. Mit PVS bekommen alle Züge nach dem ersten
+    // nur ein Nullfenster, das lediglich zeigt, ob sie alpha verbessern. Nur dann wird mit vollem Fenster wiederholt
+    #[allow(clippy::too_many_arguments)]
+    fn search_child<E: Evaluator>(&mut self, depth: u32, ply: usize, move_index: usize, alpha: Score, beta: Score, state: &SearchState<E>, thread: &mut ThreadState) -> Score {
+        if !state.options.pvs || move_index == 0 || alpha + Score(1) >= beta {
+            return -self.negamax(depth, ply, -beta, -alpha, state, thread, true);
+        }
+        thread.stats.null_window_searches += 1;
+        let eval = -self.negamax(depth, ply, -alpha - Score(1), -alpha, state, thread, true);
+        if eval > alpha && eval < beta && !state.stopped.load(Ordering::Relaxed) {
+            thread.stats.pvs_researches += 1;
+            return -self.negamax(depth, ply, -beta, -alpha, state, thread, true);
+        }
+        eval
+    }
+
+    // Matt, Patt und Remis erkennt die Suche selbst, hier wird nur die Stellung bewertet
+    fn static_eval<E: Evaluator>(&self, evaluator: &E) -> Score {
+        match self.active_player {
+            Color::White => evaluator.evaluate(self),
+            Color::Black => -evaluator.evaluate(self),
+        }
+    }
+
+    // Ruhesuche: am Horizont werden nur noch Schlagzüge und Umwandlungen gespielt, bis die Stellung ruhig ist.
+    // Im Schach gibt es kein Stand-Pat, dann werden alle Züge durchsucht
+    fn quiescence<E: Evaluator>(&mut self, ply: usize, mut alpha: Score, beta: Score, state: &SearchState<E>, thread: &mut ThreadState) -> Score {
+        // Jenseits von MAX_DEPTH wären Mattwerte nicht mehr von normalen Werten zu unterscheiden
+        if ply >= MAX_DEPTH as usize {
+            return self.static_eval(state.evaluator);
+        }
+        if thread.count_node(state, ply) {
+            return Score::DRAW;
+        }
+        let in_check = self.is_check();
+        let mut stand_pat = -Score::INFINITE;
+        let mut picker = if in_check {
+            MovePicker::new(self, None, [None; 2])
+        } else {
+            stand_pat = self.static_eval(state.evaluator);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            MovePicker::captures(self)
+        };
+
+        let mut best_eval = stand_pat;
+        let mut any_move = false;
+        while let Some(m) = picker.next(self, &thread.heuristics) {
+            any_move = true;
+            // Delta Pruning: auch mit dem geschlagenen Stein und etwas Puffer wird alpha nicht erreicht
+            if !in_check && m.promotion().is_none() && stand_pat + Score(self.captured_value(m) + DELTA_MARGIN) <= alpha {
+                continue;
+            }
+            let undo = self.make_move(m);
+            let eval = -self.quiescence(ply + 1, -beta, -alpha, state, thread);
+            self.unmake_move(m, undo);
+            if state.stopped.load(Ordering::Relaxed) {
+                return Score::DRAW;
+            }
+            best_eval = best_eval.max(eval);
+            alpha = alpha.max(eval);
+            if alpha >= beta {
+                break;
+            }
+        }
+        if in_check && !any_move {
+            return Score::mated_in(ply);
+        }
+        best_eval
+    }
+
+    fn captured_value(&self, m: Move) -> i32 {
+        if m.is_en_passant() {
+            return piece_value(&Piece::Pawn);
+        }
+        self.piece_on(m.to()).map_or(0, |(p, _)| piece_value(&p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_mate_in_one() {
+        let brett = Schach::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let result = brett.search(&SearchLimits::depth(3), &SearchOptions::default(), &tt);
+        assert_eq!(result.best_move.unwrap().to_string(), "d1d8");
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.score, Score::mate_in(1));
+    }
+
+    #[test]
+    fn no_move_when_mated_or_stalemated() {
+        let tt = TranspositionTable::new(1);
+        let mated = Schach::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        let result = mated.search(&SearchLimits::depth(3), &SearchOptions::default(), &tt);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, Score::mated_in(0));
+        assert!(result.pv.is_empty());
+        let stalemate = Schach::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = stalemate.search(&SearchLimits::depth(3), &SearchOptions::default(), &tt);
+        assert_eq!((result.best_move, result.score), (None, Score::DRAW));
+    }
+
+    #[test]
+    fn result_has_legal_pv() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        ] {
+            let brett = Schach::from_fen(fen).unwrap();
+            let tt = TranspositionTable::new(1);
+            let result = brett.search(&SearchLimits::depth(4), &SearchOptions::default(), &tt);
+            assert_eq!(Some(result.pv[0]), result.best_move);
+            assert!(result.pv.len() <= 4);
+            assert!(result.nodes > 0);
+            let mut brett = brett.clone();
+            for m in result.pv {
+                assert!(brett.get_all_legal_moves().contains(&m), "{}: {}", fen, m);
+                brett.make_move(m);
+            }
+        }
+    }
+
+    #[test]
+    fn quiescence_sees_recapture() {
+        // Ohne Ruhesuche sähe Dxd5 bei Tiefe 1 wie ein Bauerngewinn aus
+        let brett = Schach::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let result = brett.search(&SearchLimits::depth(1), &SearchOptions::default(), &tt);
+        assert_ne!(result.best_move.unwrap().to_string(), "d1d5");
+        // Dame gegen Bauer, ohne den Bauerngewinn aus Dxd5
+        assert_eq!(result.score, Score(773));
+    }
+
+    #[test]
+    fn null_move_round_trip() {
+        let fen = "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        let mut brett = Schach::from_fen(fen).unwrap();
+        let undo = brett.make_null_move();
+        let passed = Schach::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3").unwrap();
+        assert_eq!(brett.hash(), passed.hash());
+        brett.unmake_null_move(undo);
+        assert_eq!(brett.to_fen(), fen);
+        assert_eq!(brett.hash(), Schach::from_fen(fen).unwrap().hash());
+    }
+
+    #[test]
+    fn every_option_finds_mate_in_two() {
+        let brett = Schach::from_fen("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1").unwrap();
+        let none = SearchOptions {
+            random_tie_break: false,
+            null_move: false,
+            late_move_reductions: false,
+            reverse_futility: false,
+            futility: false,
+            check_extensions: false,
+            pvs: false,
+            aspiration_windows: false,
+            threads: 1,
+        };
+        let variants = [
+            SearchOptions::default(),
+            none.clone(),
+            SearchOptions { null_move: true, ..none.clone() },
+            SearchOptions { late_move_reductions: true, ..none.clone() },
+            SearchOptions { reverse_futility: true, ..none.clone() },
+            SearchOptions { futility: true, ..none.clone() },
+            SearchOptions { check_extensions: true, ..none.clone() },
+            SearchOptions { pvs: true, ..none.clone() },
+            SearchOptions { aspiration_windows: true, ..none.clone() },
+        ];
+        for options in variants {
+            let tt = TranspositionTable::new(1);
+            let result = brett.search(&SearchLimits::depth(4), &options, &tt);
+            assert_eq!(result.best_move.unwrap().to_string(), "d5f6", "{:?}", options);
+            assert_eq!(result.score, Score::mate_in(3), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn narrow_windows_keep_the_score() {
+        const POSITIONS: [&str; 6] = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        // Ohne Pruning ist der Wert bei fester Tiefe eindeutig, PVS und Aspiration dürfen ihn nicht ändern
+        let full = SearchOptions {
+            null_move: false,
+            late_move_reductions: false,
+            reverse_futility: false,
+            futility: false,
+            pvs: false,
+            aspiration_windows: false,
+            ..Default::default()
+        };
+        let narrow = SearchOptions { pvs: true, aspiration_windows: true, ..full.clone() };
+        let wide = SearchOptions { pvs: false, aspiration_windows: false, ..Default::default() };
+        let (mut default_nodes, mut wide_nodes) = (0, 0);
+        for fen in POSITIONS {
+            let brett = Schach::from_fen(fen).unwrap();
+            let search = |options: &SearchOptions| brett.search(&SearchLimits::depth(5), options, &TranspositionTable::new(4));
+            let (full, narrow) = (search(&full), search(&narrow));
+            assert_eq!(narrow.score, full.score, "{}", fen);
+            assert_eq!(full.stats, SearchStats::default());
+            assert!(narrow.stats.null_window_searches > 0);
+            assert!(narrow.stats.pvs_researches < narrow.stats.null_window_searches);
+            assert_eq!(narrow.stats.aspiration_searches, 2 + narrow.stats.aspiration_fail_lows + narrow.stats.aspiration_fail_highs);
+            default_nodes += search(&SearchOptions::default()).nodes;
+            wide_nodes += search(&wide).nodes;
+        }
+        // Einzelne Stellungen können teurer werden, über alle zusammen sparen die engen Fenster Knoten
+        assert!(default_nodes < wide_nodes, "{} {}", default_nodes, wide_nodes);
+    }
+
+    #[test]
+    fn helper_threads_share_the_table() {
+        let brett = Schach::from_fen("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1").unwrap();
+        let tt = TranspositionTable::new(4);
+        let options = SearchOptions { threads: 4, ..Default::default() };
+        let result = brett.search(&SearchLimits::depth(4), &options, &tt);
+        assert_eq!(result.best_move.unwrap().to_string(), "d5f6");
+        assert_eq!(result.score, Score::mate_in(3));
+        assert_eq!(result.depth, 4);
+    }
+
+    #[test]
+    fn handle_stops_promptly() {
+        let tt = Arc::new(TranspositionTable::new(1));
+        let handle = SearchHandle::start(Schach::new(), SearchLimits::infinite(), SearchOptions { threads: 2, ..Default::default() }, tt);
+        thread::sleep(Duration::from_millis(300));
+        assert!(!handle.is_finished());
+        let start = Instant::now();
+        handle.stop();
+        let result = handle.wait();
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert!(result.depth >= 1);
+        assert!(Schach::new().get_all_legal_moves().contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn dropping_the_handle_ends_the_search() {
+        let tt = Arc::new(TranspositionTable::new(1));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = SearchHandle::start_with(Schach::new(), SearchLimits::infinite(), SearchOptions::default(), tt, Arc::new(HandCrafted::default()), |_| (), move |result| tx.send(result.best_move).unwrap());
+        drop(handle);
+        // Der Thread ist schon beendet und hat sein Ergebnis verworfen
+        assert_eq!(rx.try_recv(), Err(std::sync::mpsc::TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn reports_every_iteration() {
+        let brett = Schach::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let tt = TranspositionTable::new(1);
+        let infos = std::sync::Mutex::new(Vec::new());
+        let result = brett.search_with(&SearchLimits::depth(5), &SearchOptions::default(), &tt, &*HAND_CRAFTED, &AtomicBool::new(false), &|info| infos.lock().unwrap().push(info.clone()));
+        let infos = infos.into_inner().unwrap();
+        assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+        assert!(infos.windows(2).all(|w| w[0].nodes <= w[1].nodes && w[0].seldepth <= w[1].seldepth));
+        let last = infos.last().unwrap();
+        assert!(last.seldepth > 5);
+        assert!(last.hashfull > 0);
+        assert_eq!((last.score, &last.pv), (result.score, &result.pv));
+    }
+
+    #[test]
+    fn searches_with_other_evaluators() {
+        // Bewertet nur, ob ein weißer Springer auf f3 steht
+        struct KnightOnF3;
+        impl Evaluator for KnightOnF3 {
+            fn evaluate(&self, brett: &Schach) -> Score {
+                let f3 = 5 + 8 * 5;
+                Score(1000 * (brett.bitboards(&Color::White).4 >> f3 & 1) as i32)
+            }
+        }
+        let brett = Schach::new();
+        let tt = TranspositionTable::new(1);
+        let result = brett.search_with(&SearchLimits::depth(2), &SearchOptions::default(), &tt, &KnightOnF3, &AtomicBool::new(false), &|_| ());
+        assert_eq!(result.best_move.unwrap().to_string(), "g1f3");
+        assert_eq!(result.score, Score(1000));
+    }
+
+    #[test]
+    fn stops_on_movetime() {
+        let brett = Schach::new();
+        let tt = TranspositionTable::new(1);
+        let start = Instant::now();
+        let result = brett.search(&SearchLimits::movetime(Duration::from_millis(200)), &SearchOptions::default(), &tt);
+        assert!(start.elapsed() < Duration::from_millis(1000));
+        assert!(result.elapsed <= start.elapsed());
+        assert!(brett.get_all_legal_moves().contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn stops_on_node_limit() {
+        let tt = TranspositionTable::new(1);
+        let limits = SearchLimits { nodes: Some(5000), ..Default::default() };
+        let result = Schach::new().search(&limits, &SearchOptions::default(), &tt);
+        // Auch die noch nicht übertragenen Knoten des Threads sind mitgezählt
+        assert_eq!(result.nodes, 5000);
+        assert!(result.depth >= 1);
+    }
+
+    #[test]
+    fn time_budget_from_clock() {
+        let limits = SearchLimits {
+            wtime: Some(Duration::from_secs(60)),
+            btime: Some(Duration::from_secs(1)),
+            winc: Duration::from_secs(2),
+            movestogo: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(limits.time_budget(&Color::White), Some(Duration::from_millis(4500)));
+        assert_eq!(limits.time_budget(&Color::Black), Some(Duration::from_millis(50)));
+        assert_eq!(SearchLimits::infinite().time_budget(&Color::White), None);
+        assert_eq!(SearchLimits::depth(5).time_budget(&Color::White), None);
+    }
+}
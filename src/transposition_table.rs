@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::moves::Move;
+use crate::score::Score;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    // Der Wert ist genau
+    Exact,
+    // Beta-Cutoff, der echte Wert ist mindestens so groß
+    Lower,
+    // Kein Zug hat alpha verbessert, der echte Wert ist höchstens so groß
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub depth: u8,
+    pub score: Score,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+// Ein Eintrag besteht aus zwei Wörtern, gespeichert wird key ^ data und data.
+// Schreiben mehrere Threads gleichzeitig, passt der Schlüssel danach nicht mehr
+// zu den Daten und der Eintrag wird beim Lesen einfach verworfen.
+// Aufbau von data: Bits 0-31 Wert, 32-39 Tiefe, 40-41 Bound, 42-60 Zug, 61-63 Generation
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// Feste Größe, wird über alle Iterationen und Züge einer Partie weiterverwendet
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        // Zweierpotenz, damit der Index mit einer Maske bestimmt werden kann
+        let count = 1 << (usize::BITS - 1 - count.leading_zeros());
+        Self {
+            slots: (0..count).map(|_| Slot { key: AtomicU64::new(0), data: AtomicU64::new(0) }).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    // Vor jeder neuen Suche aufrufen, Einträge älterer Suchen werden dann bevorzugt ersetzt
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    // Anteil der Einträge aus der aktuellen Suche in Promille, geschätzt aus den ersten 1000 Slots
+    pub fn hashfull(&self) -> u32 {
+        let generation = self.generation.load(Ordering::Relaxed) as u64 & 0b111;
+        let sample = &self.slots[..self.slots.len().min(1000)];
+        let used = sample.iter().filter(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            data != 0 && data >> 61 == generation
+        }).count();
+        (used * 1000 / sample.len()) as u32
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        &self.slots[key as usize & (self.slots.len() - 1)]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        if data == 0 || slot.key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        let best_move = (data >> 42 & 0x7ffff) as u32;
+        Some(Entry {
+            score: Score(data as u32 as i32),
+            depth: (data >> 32) as u8,
+            bound: match data >> 40 & 0b11 {
+                0 => Bound::Exact,
+                1 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+            best_move: if best_move == 0 { None } else { Some(Move::from_bits(best_move)) },
+        })
+    }
+
+    // Ersetzt wird, wenn der Eintrag zur selben Stellung gehört, aus einer älteren Suche stammt
+    // oder nicht tiefer gesucht wurde. Ohne neuen besten Zug bleibt der alte erhalten
+    pub fn store(&self, key: u64, depth: u8, score: Score, bound: Bound, best_move: Option<Move>) {
+        let slot = self.slot(key);
+        let generation = self.generation.load(Ordering::Relaxed) as u64 & 0b111;
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let same_position = slot.key.load(Ordering::Relaxed) ^ old_data == key;
+        if !same_position && old_data >> 61 == generation && (old_data >> 32) as u8 > depth {
+            return;
+        }
+
+        let mut move_bits = best_move.map_or(0, |m| m.bits() as u64);
+        if move_bits == 0 && same_position {
+            move_bits = old_data >> 42 & 0x7ffff;
+        }
+        let bound_bits = match bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let data = score.0 as u32 as u64 | (depth as u64) << 32 | bound_bits << 40 | move_bits << 42 | generation << 61;
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let m = Move::new(52, 36, None, Move::DOUBLE_PUSH);
+        tt.store(0x1234_5678_9abc_def0, 5, Score(-125), Bound::Lower, Some(m));
+        let entry = tt.probe(0x1234_5678_9abc_def0).unwrap();
+        assert_eq!(entry, Entry { depth: 5, score: Score(-125), bound: Bound::Lower, best_move: Some(m) });
+        assert_eq!(tt.probe(0x1234_5678_9abc_def1), None);
+    }
+
+    #[test]
+    fn deeper_entries_survive_within_a_search() {
+        let tt = TranspositionTable::new(1);
+        let len = tt.slots.len() as u64;
+        tt.store(7, 6, Score(100), Bound::Exact, None);
+        // Andere Stellung im selben Slot, aber flacher gesucht
+        tt.store(7 + len, 2, Score(200), Bound::Exact, None);
+        assert_eq!(tt.probe(7).unwrap().depth, 6);
+        assert_eq!(tt.probe(7 + len), None);
+
+        tt.new_search();
+        tt.store(7 + len, 2, Score(200), Bound::Exact, None);
+        assert_eq!(tt.probe(7), None);
+        assert_eq!(tt.probe(7 + len).unwrap().score, Score(200));
+    }
+
+    #[test]
+    fn hashfull_counts_the_current_search() {
+        let tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+        for key in 0..500 {
+            tt.store(key, 1, Score(0), Bound::Exact, None);
+        }
+        assert_eq!(tt.hashfull(), 500);
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn keeps_best_move_of_same_position() {
+        let tt = TranspositionTable::new(1);
+        let m = Move::new(6, 21, None, 0);
+        tt.store(42, 3, Score(50), Bound::Lower, Some(m));
+        tt.store(42, 4, Score(25), Bound::Upper, None);
+        let entry = tt.probe(42).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.best_move), (4, Bound::Upper, Some(m)));
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::evaluation::Evaluator;
+use crate::schach::Schach;
+use crate::search::{SearchHandle, SearchInfo, SearchLimits, SearchOptions, SearchResult};
+use crate::transposition_table::TranspositionTable;
+
+// Obergrenzen der Optionen, wie sie nach "uci" angekündigt werden
+const MAX_HASH_MB: usize = 4096;
+const MAX_THREADS: usize = 256;
+
+// Minimale UCI-Schnittstelle, damit die Engine in anderen Oberflächen gegen andere Engines spielen kann
+pub fn run<E: Evaluator + Send + 'static>(evaluator: Arc<E>) {
+    let mut brett = Schach::new();
+    let mut options = SearchOptions::default();
+    let mut tt = Arc::new(TranspositionTable::new(64));
+    // Die laufende Suche und ob sie mit "go infinite" gestartet wurde
+    let mut search: Option<(SearchHandle, bool)> = None;
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"uci") => {
+                println!("id name Schach");
+                println!("option name Hash type spin default 64 min 1 max {}", MAX_HASH_MB);
+                println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+                println!("uciok");
+            },
+            Some(&"isready") => println!("readyok"),
+            Some(&"setoption") => {
+                // Eine laufende Suche wird verworfen, ohne noch bestmove auszugeben
+                search = None;
+                match parse_option(&tokens[1..]) {
+                    Some(("Hash", value)) => tt = Arc::new(TranspositionTable::new(value.clamp(1, MAX_HASH_MB))),
+                    Some(("Threads", value)) => options.threads = value.clamp(1, MAX_THREADS),
+                    _ => eprintln!("unknown option: {}", line),
+                }
+            },
+            Some(&"ucinewgame") => {
+                search = None;
+                tt.clear();
+                brett = Schach::new();
+            },
+            Some(&"position") => {
+                search = None;
+                match parse_position(&tokens[1..]) {
+                    Some(position) => brett = position,
+                    None => eprintln!("invalid position: {}", line),
+                }
+            },
+            Some(&"go") => {
+                // Eine noch laufende Suche gibt vorher ihren Zug aus
+                stop_search(&mut search);
+                let limits = parse_go(&tokens[1..]);
+                let infinite = limits.infinite;
+                let handle = SearchHandle::start_with(brett.clone(), limits, options.clone(), Arc::clone(&tt), Arc::clone(&evaluator), |info| {
+                    println!("{}", info_line(info));
+                }, move |result| {
+                    // Nach "go infinite" darf bestmove erst nach "stop" kommen, auch wenn die Suche vorher endet
+                    if !infinite {
+                        print_bestmove(result);
+                    }
+                });
+                search = Some((handle, infinite));
+            },
+            Some(&"stop") => stop_search(&mut search),
+            Some(&"quit") => {
+                stop_search(&mut search);
+                break;
+            },
+            _ => (),
+        }
+    }
+}
+
+// Bricht die laufende Suche ab und wartet auf sie. Ohne "infinite" hat on_finish den Zug schon ausgegeben
+fn stop_search(search: &mut Option<(SearchHandle, bool)>) {
+    if let Some((handle, infinite)) = search.take() {
+        handle.stop();
+        let result = handle.wait();
+        if infinite {
+            print_bestmove(&result);
+        }
+    }
+}
+
+fn print_bestmove(result: &SearchResult) {
+    match result.best_move {
+        Some(m) => println!("bestmove {}", m),
+        None => println!("bestmove 0000"),
+    }
+}
+
+// setoption name <Name> value <Wert>
+fn parse_option<'a>(tokens: &[&'a str]) -> Option<(&'a str, usize)> {
+    match tokens {
+        ["name", name, "value", value] => Some((name, value.parse().ok()?)),
+        _ => None,
+    }
+}
+
+// position startpos|fen <FEN> [moves <Züge>]
+fn parse_position(tokens: &[&str]) -> Option<Schach> {
+    let moves_at = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+    let mut brett = match tokens.first() {
+        Some(&"startpos") => Schach::new(),
+        Some(&"fen") => Schach::from_fen(&tokens[1..moves_at].join(" ")).ok()?,
+        _ => return None,
+    };
+    for token in tokens.iter().skip(moves_at + 1) {
+        let m = brett.get_all_legal_moves().into_iter().find(|m| m.to_string() == *token)?;
+        brett.make_move(m);
+    }
+    Some(brett)
+}
+
+fn parse_go(tokens: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let millis = |value: Option<&&str>| value.and_then(|v| v.parse().ok()).map(Duration::from_millis);
+    let mut i = 0;
+    while i < tokens.len() {
+        let value = tokens.get(i + 1);
+        match tokens[i] {
+            "depth" => limits.depth = value.and_then(|v| v.parse().ok()),
+            "nodes" => limits.nodes = value.and_then(|v| v.parse().ok()),
+            "movestogo" => limits.movestogo = value.and_then(|v| v.parse().ok()),
+            "movetime" => limits.movetime = millis(value),
+            "wtime" => limits.wtime = millis(value),
+            "btime" => limits.btime = millis(value),
+            "winc" => limits.winc = millis(value).unwrap_or_default(),
+            "binc" => limits.binc = millis(value).unwrap_or_default(),
+            "infinite" => {
+                limits.infinite = true;
+                i += 1;
+                continue;
+            },
+            _ => {
+                i += 1;
+                continue;
+            },
+        }
+        i += 2;
+    }
+    limits
+}
+
+fn info_line(info: &SearchInfo) -> String {
+    let score = match info.score.mate_moves() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score.0),
+    };
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_string()).collect();
+    format!("info depth {} seldepth {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+        info.depth, info.seldepth, score, info.nodes, info.nps, info.hashfull, info.elapsed.as_millis(), pv.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score::Score;
+
+    #[test]
+    fn position_with_moves() {
+        let tokens: Vec<&str> = "startpos moves e2e4 e7e5 g1f3".split_whitespace().collect();
+        let brett = parse_position(&tokens).unwrap();
+        assert_eq!(brett.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        let tokens: Vec<&str> = "fen 4k3/1P6/8/8/8/8/8/4K3 w - - 0 1 moves b7b8n".split_whitespace().collect();
+        assert_eq!(parse_position(&tokens).unwrap().to_fen(), "1N2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        let tokens: Vec<&str> = "startpos moves e2e5".split_whitespace().collect();
+        assert!(parse_position(&tokens).is_none());
+    }
+
+    #[test]
+    fn go_parameters() {
+        let tokens: Vec<&str> = "wtime 60000 btime 55000 winc 1000 binc 1000 movestogo 20".split_whitespace().collect();
+        let limits = parse_go(&tokens);
+        assert_eq!(limits.wtime, Some(Duration::from_secs(60)));
+        assert_eq!(limits.btime, Some(Duration::from_secs(55)));
+        assert_eq!(limits.winc, Duration::from_secs(1));
+        assert_eq!(limits.movestogo, Some(20));
+        assert!(!limits.infinite);
+
+        let tokens: Vec<&str> = "infinite depth 7".split_whitespace().collect();
+        let limits = parse_go(&tokens);
+        assert!(limits.infinite);
+        assert_eq!(limits.depth, Some(7));
+    }
+
+    #[test]
+    fn info_lines() {
+        let brett = Schach::new();
+        let pv: Vec<_> = brett.get_all_legal_moves().into_iter().filter(|m| m.to_string() == "e2e4").collect();
+        let mut info = SearchInfo {
+            depth: 6,
+            seldepth: 11,
+            score: Score(-35),
+            nodes: 12000,
+            nps: 400000,
+            hashfull: 17,
+            elapsed: Duration::from_millis(30),
+            pv,
+        };
+        assert_eq!(info_line(&info), "info depth 6 seldepth 11 score cp -35 nodes 12000 nps 400000 hashfull 17 time 30 pv e2e4");
+        info.score = Score::mated_in(4);
+        assert!(info_line(&info).contains("score mate -2 "));
+    }
+}
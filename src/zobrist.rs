@@ -0,0 +1,101 @@
+use crate::schach::{Color, Piece};
+
+// Zufallszahlen für das inkrementelle Hashen der Stellung.
+// Fester Seed, damit die Schlüssel bei jedem Programmstart gleich sind
+pub struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    castle_rights: [u64; 16],
+    en_passant_file: [u64; 8],
+}
+
+impl Default for ZobristKeys {
+    fn default() -> Self {
+        let mut rng = fastrand::Rng::with_seed(0x5eed_5c4a_c400_0001);
+        let mut pieces = [[0; 64]; 12];
+        for piece in pieces.iter_mut() {
+            for key in piece.iter_mut() {
+                *key = rng.u64(..);
+            }
+        }
+        let mut castle_rights = [0; 16];
+        for key in castle_rights.iter_mut() {
+            *key = rng.u64(..);
+        }
+        let mut en_passant_file = [0; 8];
+        for key in en_passant_file.iter_mut() {
+            *key = rng.u64(..);
+        }
+        Self {
+            pieces,
+            black_to_move: rng.u64(..),
+            castle_rights,
+            en_passant_file,
+        }
+    }
+}
+
+impl ZobristKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn piece(&self, p: &Piece, c: &Color, square: u64) -> u64 {
+        self.pieces[p.index() + 6 * c.index()][square as usize]
+    }
+
+    pub fn black_to_move(&self) -> u64 {
+        self.black_to_move
+    }
+
+    // castle ist die Maske der unbewegten König- und Turmfelder, gehasht werden nur die
+    // daraus folgenden vier Rochaderechte
+    pub fn castle(&self, castle: u64) -> u64 {
+        let rights = [(60, 63), (60, 56), (4, 7), (4, 0)]
+            .iter()
+            .enumerate()
+            .filter(|(_, (king, rook))| castle >> king & 1 == 1 && castle >> rook & 1 == 1)
+            .fold(0, |rights, (i, _)| rights | 1 << i);
+        self.castle_rights[rights]
+    }
+
+    pub fn en_passant(&self, en_passant: Option<(i32, i32)>) -> u64 {
+        match en_passant {
+            Some((x, _)) => self.en_passant_file[x as usize],
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schach::{assert_consistent, Schach};
+
+    #[test]
+    fn incremental_matches_fresh() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        ] {
+            assert_consistent(&mut Schach::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn transpositions_share_key() {
+        let mut a = Schach::new();
+        let mut b = Schach::new();
+        for m in ["g1f3", "g8f6", "b1c3"] {
+            let m = a.get_all_legal_moves().into_iter().find(|x| x.to_string() == m).unwrap();
+            a.make_move(m);
+        }
+        for m in ["b1c3", "g8f6", "g1f3"] {
+            let m = b.get_all_legal_moves().into_iter().find(|x| x.to_string() == m).unwrap();
+            b.make_move(m);
+        }
+        assert_eq!(a.hash(), b.hash());
+        assert_ne!(a.hash(), Schach::new().hash());
+    }
+}